@@ -0,0 +1,58 @@
+#![no_main]
+
+// cargo fuzz run run_rom
+// the first byte selects the quirks and the fault policy, the second one the pressed keys
+// the rest is the rom
+
+use chip8::{
+    fault::{FaultKind, FaultPolicy},
+    machine::{Machine, MEMORY_SIZE},
+    options::{Config, Options},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 || data.len() - 2 > MEMORY_SIZE - 0x200 {
+        return;
+    }
+
+    let mut machine = Machine::new(&Options {
+        config: Config {
+            shift_behaviour: data[0] & 0x1 != 0,
+            draw_behaviour: data[0] & 0x2 != 0,
+            store_behaviour: data[0] & 0x4 != 0,
+        },
+        seed: Some(0),
+        ..Options::default()
+    });
+
+    for kind in FaultKind::ALL.iter() {
+        *machine.fault_policies.get_mut(*kind) = FaultPolicy::ALL[(data[0] as usize >> 3) % 3];
+    }
+
+    machine.load_rom(&data[2..]).unwrap();
+
+    for (index, pressed) in machine.keys.iter_mut().enumerate() {
+        *pressed = (data[1] >> (index % 8)) & 0x1 != 0;
+    }
+
+    for cycle in 0..10_000 {
+        if !machine.is_running() {
+            break;
+        }
+
+        let pc = machine.pc;
+
+        if machine.step().is_err() {
+            // a trapped fault stops before the instruction
+            assert_eq!(machine.pc, pc);
+            break;
+        }
+
+        if cycle % 60 == 0 {
+            machine.update_timers(1.0 / 60.0);
+        }
+
+        assert!(machine.sp <= machine.stack.len());
+    }
+});
@@ -0,0 +1,566 @@
+// libretro core, lets RetroArch and other libretro frontends run chip8 roms
+// the frontend handles windows, input devices, save states and rewind through the retro_* api
+
+// every exported function follows the safety rules of libretro.h
+#![allow(clippy::missing_safety_doc)]
+
+mod ffi;
+
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_uint, c_void},
+    ptr, slice,
+    sync::Mutex,
+};
+
+use chip8::{
+    fault::ExecutionFault,
+    frontend::{self, Audio, Display, Input},
+    machine::{Machine, MEMORY_SIZE, STATE_SIZE, S_HEIGHT, S_WIDTH},
+    options::{Config, Options},
+};
+
+use ffi::*;
+
+const FPS: f64 = 60.0;
+const SAMPLE_RATE: f64 = 44100.0;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FPS) as usize;
+
+// square wave of the sdl frontend
+const TONE_PERIOD: usize = 100; // samples, 441 hz
+const VOLUME: i16 = 0x1800;
+
+macro_rules! cstr {
+    ($s:expr) => {
+        concat!($s, "\0").as_ptr() as *const c_char
+    };
+}
+
+// core options, the first value is the default and matches the chip8 profile
+const VARIABLES: [(&str, &str); 4] = [
+    (
+        "chip8_shift_quirk\0",
+        "8XY6/8XYE shift VY; enabled|disabled\0",
+    ),
+    (
+        "chip8_wrap_quirk\0",
+        "Sprites wrap around the screen; enabled|disabled\0",
+    ),
+    (
+        "chip8_store_quirk\0",
+        "FX55/FX65 increment I; enabled|disabled\0",
+    ),
+    (
+        "chip8_cycles\0",
+        "Instructions per frame; 60|30|100|200|500|1000\0",
+    ),
+];
+
+// keys 0 to F on the keyboard, laid out like in the sdl frontend
+//   1 2 3 4
+//   q w e r
+//   a s d f
+//   z x c v
+const KEY_CHARS: [char; 16] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+// retropad buttons, directions follow the 2/4/6/8 layout most games use
+// the whole keypad is also on the keyboard, see KEY_CHARS
+const JOYPAD_MAP: [(c_uint, usize, &str); 14] = [
+    (DEVICE_ID_JOYPAD_UP, 0x2, "Key 2 (up)\0"),
+    (DEVICE_ID_JOYPAD_DOWN, 0x8, "Key 8 (down)\0"),
+    (DEVICE_ID_JOYPAD_LEFT, 0x4, "Key 4 (left)\0"),
+    (DEVICE_ID_JOYPAD_RIGHT, 0x6, "Key 6 (right)\0"),
+    (DEVICE_ID_JOYPAD_A, 0x5, "Key 5\0"),
+    (DEVICE_ID_JOYPAD_B, 0x0, "Key 0\0"),
+    (DEVICE_ID_JOYPAD_X, 0xA, "Key A\0"),
+    (DEVICE_ID_JOYPAD_Y, 0xF, "Key F\0"),
+    (DEVICE_ID_JOYPAD_L, 0x1, "Key 1\0"),
+    (DEVICE_ID_JOYPAD_R, 0x3, "Key 3\0"),
+    (DEVICE_ID_JOYPAD_L2, 0x7, "Key 7\0"),
+    (DEVICE_ID_JOYPAD_R2, 0x9, "Key 9\0"),
+    (DEVICE_ID_JOYPAD_SELECT, 0xB, "Key B\0"),
+    (DEVICE_ID_JOYPAD_START, 0xC, "Key C\0"),
+];
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+// loaded game
+struct Core {
+    machine: Machine,
+    rom: Vec<u8>, // kept for retro_reset
+    cycles_per_frame: u32,
+    fault: Option<ExecutionFault>, // trapped fault, the machine stays frozen until reset
+    audio: SquareWave,
+    frame: Vec<u32>, // XRGB8888 pixels
+}
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap()
+}
+
+unsafe fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(environment) => environment(cmd, data),
+        None => false,
+    }
+}
+
+// show a message on screen, the frontend copies the text
+unsafe fn show_message(text: &str) {
+    let text = CString::new(text.replace('\0', "")).unwrap();
+    let mut message = Message {
+        msg: text.as_ptr(),
+        frames: 180,
+    };
+
+    environment(
+        ENVIRONMENT_SET_MESSAGE,
+        &mut message as *mut Message as *mut c_void,
+    );
+}
+
+unsafe fn variable(key: &str) -> Option<String> {
+    let mut variable = Variable {
+        key: key.as_ptr() as *const c_char,
+        value: ptr::null(),
+    };
+
+    if !environment(
+        ENVIRONMENT_GET_VARIABLE,
+        &mut variable as *mut Variable as *mut c_void,
+    ) || variable.value.is_null()
+    {
+        return None;
+    }
+
+    Some(
+        CStr::from_ptr(variable.value)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+unsafe fn apply_variables(core: &mut Core) {
+    let enabled = |key| variable(key).as_deref() != Some("disabled");
+
+    core.machine.config = Config {
+        shift_behaviour: enabled(VARIABLES[0].0),
+        draw_behaviour: enabled(VARIABLES[1].0),
+        store_behaviour: enabled(VARIABLES[2].0),
+    };
+
+    if let Some(cycles) = variable(VARIABLES[3].0).and_then(|value| value.parse().ok()) {
+        core.cycles_per_frame = cycles;
+    }
+}
+
+struct Keypad(Option<InputStateFn>);
+
+impl Input for Keypad {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        let input_state = match self.0 {
+            Some(input_state) => input_state,
+            None => return,
+        };
+
+        // libretro keyboard ids are ascii for letters and digits
+        for (pressed, c) in keys.iter_mut().zip(KEY_CHARS.iter()) {
+            *pressed = unsafe { input_state(0, DEVICE_KEYBOARD, 0, *c as c_uint) } != 0;
+        }
+
+        for (id, key, _) in JOYPAD_MAP.iter() {
+            keys[*key] |= unsafe { input_state(0, DEVICE_JOYPAD, 0, *id) } != 0;
+        }
+    }
+}
+
+#[derive(Default)]
+struct SquareWave {
+    beeping: bool,
+    phase: usize,
+    samples: Vec<i16>, // interleaved stereo
+}
+
+impl SquareWave {
+    // samples of one frame, silence while not beeping
+    fn render(&mut self) -> &[i16] {
+        self.samples.clear();
+
+        for _ in 0..SAMPLES_PER_FRAME {
+            let value = match (self.beeping, self.phase < TONE_PERIOD / 2) {
+                (false, _) => 0,
+                (true, true) => VOLUME,
+                (true, false) => -VOLUME,
+            };
+
+            self.phase = (self.phase + 1) % TONE_PERIOD;
+            self.samples.extend_from_slice(&[value, value]);
+        }
+
+        &self.samples
+    }
+}
+
+impl Audio for SquareWave {
+    fn set_beeping(&mut self, beeping: bool) {
+        self.beeping = beeping;
+    }
+}
+
+// frames are always high resolution, low resolution pixels are doubled
+struct Video<'a> {
+    video_refresh: Option<VideoRefreshFn>,
+    frame: &'a mut Vec<u32>,
+}
+
+impl Display for Video<'_> {
+    fn present(&mut self, machine: &Machine) {
+        let x_scale = S_WIDTH / machine.width;
+        let y_scale = S_HEIGHT / machine.height;
+
+        self.frame.clear();
+
+        for y in 0..S_HEIGHT {
+            for x in 0..S_WIDTH {
+                let [r, g, b] = machine.pixel_color(x / x_scale, y / y_scale).as_rgb();
+                self.frame
+                    .push((r as u32) << 16 | (g as u32) << 8 | b as u32);
+            }
+        }
+
+        if let Some(video_refresh) = self.video_refresh {
+            unsafe {
+                video_refresh(
+                    self.frame.as_ptr() as *const c_void,
+                    S_WIDTH as c_uint,
+                    S_HEIGHT as c_uint,
+                    S_WIDTH * 4,
+                )
+            };
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+
+    let mut variables: Vec<Variable> = VARIABLES
+        .iter()
+        .map(|(key, value)| Variable {
+            key: key.as_ptr() as *const c_char,
+            value: value.as_ptr() as *const c_char,
+        })
+        .collect();
+
+    variables.push(Variable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+
+    callback(
+        ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+// audio is sent in batches
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: cstr!("Chip8/SuperChip"),
+        library_version: cstr!(env!("CARGO_PKG_VERSION")),
+        valid_extensions: cstr!("ch8|sc8|c8"),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: S_WIDTH as c_uint,
+            base_height: S_HEIGHT as c_uint,
+            max_width: S_WIDTH as c_uint,
+            max_height: S_HEIGHT as c_uint,
+            aspect_ratio: 2.0,
+        },
+        timing: SystemTiming {
+            fps: FPS,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+
+    let rom = slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+
+    let mut pixel_format = PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut pixel_format as *mut _ as *mut c_void,
+    ) {
+        return false;
+    }
+
+    // rewind is done by the frontend with save states
+    let options = Options {
+        history_size: 0,
+        ..Options::default()
+    };
+
+    let mut machine = Machine::new(&options);
+
+    if let Err(err) = machine.load_rom(&rom) {
+        show_message(&err);
+        return false;
+    }
+
+    let mut core = Core {
+        machine,
+        rom,
+        cycles_per_frame: options.cycles_per_frame,
+        fault: None,
+        audio: SquareWave::default(),
+        frame: Vec::with_capacity(S_WIDTH * S_HEIGHT),
+    };
+
+    apply_variables(&mut core);
+
+    let mut descriptors: Vec<InputDescriptor> = JOYPAD_MAP
+        .iter()
+        .map(|(id, _, description)| InputDescriptor {
+            port: 0,
+            device: DEVICE_JOYPAD,
+            index: 0,
+            id: *id,
+            description: description.as_ptr() as *const c_char,
+        })
+        .collect();
+
+    descriptors.push(InputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+
+    environment(
+        ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+
+    *CORE.lock().unwrap() = Some(core);
+
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.machine.load_rom(&core.rom).unwrap();
+        core.fault = None;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let callbacks = callbacks();
+
+    let mut core = CORE.lock().unwrap();
+    let core = match core.as_mut() {
+        Some(core) => core,
+        None => return,
+    };
+
+    let mut updated = false;
+    if environment(
+        ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut bool as *mut c_void,
+    ) && updated
+    {
+        apply_variables(core);
+    }
+
+    if let Some(input_poll) = callbacks.input_poll {
+        input_poll();
+    }
+
+    let mut video = Video {
+        video_refresh: callbacks.video_refresh,
+        frame: &mut core.frame,
+    };
+
+    if core.fault.is_some() {
+        // frozen, keep showing the last frame
+        core.audio.set_beeping(false);
+        video.present(&core.machine);
+    } else if let Err(fault) = frontend::run_frame(
+        &mut core.machine,
+        core.cycles_per_frame,
+        1.0 / FPS,
+        &mut Keypad(callbacks.input_state),
+        &mut core.audio,
+        &mut video,
+    ) {
+        show_message(&format!(
+            "{} at {:#x}, reset to continue",
+            fault, core.machine.pc
+        ));
+
+        core.fault = Some(fault);
+        video.present(&core.machine);
+    }
+
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        let samples = core.audio.render();
+        audio_sample_batch(samples.as_ptr(), samples.len() / 2);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = CORE.lock().unwrap();
+
+    match core.as_ref() {
+        Some(core) if size >= STATE_SIZE => {
+            let state = core.machine.save_state();
+            ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+            true
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut core = CORE.lock().unwrap();
+
+    let core = match core.as_mut() {
+        Some(core) if size >= STATE_SIZE => core,
+        _ => return false,
+    };
+
+    let state = slice::from_raw_parts(data as *const u8, STATE_SIZE);
+
+    match core.machine.load_state(state) {
+        Ok(()) => {
+            // the loaded state replaces the faulted one
+            core.fault = None;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+// chip8 memory for cheat searches and achievements
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match CORE.lock().unwrap().as_mut() {
+        Some(core) if id == MEMORY_SYSTEM_RAM => core.machine.memory.as_mut_ptr() as *mut c_void,
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match CORE.lock().unwrap().as_ref() {
+        Some(_) if id == MEMORY_SYSTEM_RAM => MEMORY_SIZE,
+        _ => 0,
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use super::disassembler::disassemble;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EdgeKind {
+    Fallthrough, // next instruction, also used for the return address of a call
+    Jump,        // 1NNN
+    Indirect,    // BNNN, only the base address is known
+    Skip,        // taken branch of a skip instruction
+    Call,        // 2NNN
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ByteKind {
+    Unknown, // never reached by the analysis, most likely padding
+    Code,
+    Data, // referenced by ANNN, most likely sprite data
+}
+
+pub struct BasicBlock {
+    pub start: u16,
+    pub end: u16, // exclusive
+    pub edges: Vec<(EdgeKind, u16)>,
+}
+
+// static control flow graph of a rom
+pub struct Analysis {
+    pub entry: u16,
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    pub subroutines: BTreeSet<u16>,
+    bytes: Vec<ByteKind>, // kind of every byte in memory
+}
+
+impl Analysis {
+    // analyze memory[entry..end], everything outside of it is treated as unreachable
+    pub fn new(memory: &[u8], entry: u16, end: usize) -> Self {
+        let end = end.min(memory.len());
+
+        let mut bytes = vec![ByteKind::Unknown; memory.len()];
+        let mut successors = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut subroutines = BTreeSet::new();
+
+        let mut worklist = vec![entry];
+        leaders.insert(entry);
+
+        // first pass, find every reachable instruction and where basic blocks start
+        while let Some(addr) = worklist.pop() {
+            let index = addr as usize;
+
+            if index + 1 >= end || bytes[index] == ByteKind::Code || successors.contains_key(&addr)
+            {
+                continue;
+            }
+
+            let upper = memory[index];
+            let lower = memory[index + 1];
+
+            if disassemble(upper, lower).is_none() {
+                // executing this would raise an error, stop following this path
+                continue;
+            }
+
+            bytes[index] = ByteKind::Code;
+            bytes[index + 1] = ByteKind::Code;
+
+            let edges = Self::successors(addr, upper, lower);
+
+            let ends_block = !matches!(edges.as_slice(), [(EdgeKind::Fallthrough, _)]);
+
+            for (kind, target) in edges.iter() {
+                match kind {
+                    EdgeKind::Call => {
+                        subroutines.insert(*target);
+                        leaders.insert(*target);
+                    }
+                    _ if ends_block => {
+                        leaders.insert(*target);
+                    }
+                    _ => {}
+                }
+
+                worklist.push(*target);
+            }
+
+            // ANNN usually points to sprite data
+            if upper >> 4 == 0xA {
+                let target = ((((upper & 0xF) as u16) << 8) | lower as u16) as usize;
+
+                if target < end && bytes[target] == ByteKind::Unknown {
+                    bytes[target] = ByteKind::Data;
+                }
+            }
+
+            successors.insert(addr, edges);
+        }
+
+        // everything that isnt code but is referenced by ANNN is sprite data
+        // spread data markers forward until the next code byte or jump target,
+        // targets that couldnt be followed are still where some instruction starts
+        let mut in_data = false;
+        for (index, kind) in bytes.iter_mut().enumerate().take(end).skip(entry as usize) {
+            match *kind {
+                ByteKind::Code => in_data = false,
+                ByteKind::Data => in_data = true,
+                ByteKind::Unknown if leaders.contains(&(index as u16)) => in_data = false,
+                ByteKind::Unknown if in_data => *kind = ByteKind::Data,
+                ByteKind::Unknown => {}
+            }
+        }
+
+        // second pass, group instructions into basic blocks
+        let mut blocks = BTreeMap::new();
+
+        for &start in leaders.iter() {
+            if !successors.contains_key(&start) {
+                continue;
+            }
+
+            let mut addr = start;
+
+            let edges = loop {
+                let edges = &successors[&addr];
+                let next = addr.wrapping_add(2);
+
+                let falls_into_leader = matches!(edges.as_slice(), [(EdgeKind::Fallthrough, _)])
+                    && (leaders.contains(&next) || !successors.contains_key(&next));
+
+                if falls_into_leader || !matches!(edges.as_slice(), [(EdgeKind::Fallthrough, _)]) {
+                    break edges.clone();
+                }
+
+                addr = next;
+            };
+
+            blocks.insert(
+                start,
+                BasicBlock {
+                    start,
+                    end: addr + 2,
+                    edges: edges
+                        .into_iter()
+                        .filter(|(_, target)| successors.contains_key(target))
+                        .collect(),
+                },
+            );
+        }
+
+        Self {
+            entry,
+            blocks,
+            subroutines,
+            bytes,
+        }
+    }
+
+    fn successors(addr: u16, upper: u8, lower: u8) -> Vec<(EdgeKind, u16)> {
+        let next = addr.wrapping_add(2);
+        let skip = addr.wrapping_add(4);
+
+        let target = (((upper & 0xF) as u16) << 8) | lower as u16;
+
+        match (upper >> 4, lower) {
+            (0x0, 0xEE) | (0x0, 0xFD) => vec![], // RET and EXIT
+            (0x1, _) => vec![(EdgeKind::Jump, target)],
+            (0xB, _) => vec![(EdgeKind::Indirect, target)],
+            (0x2, _) => vec![(EdgeKind::Call, target), (EdgeKind::Fallthrough, next)],
+            (0x3, _) | (0x4, _) | (0x5, _) | (0x9, _) | (0xE, 0x9E) | (0xE, 0xA1) => {
+                vec![(EdgeKind::Fallthrough, next), (EdgeKind::Skip, skip)]
+            }
+            _ => vec![(EdgeKind::Fallthrough, next)],
+        }
+    }
+
+    pub fn byte_kind(&self, addr: usize) -> ByteKind {
+        self.bytes.get(addr).copied().unwrap_or(ByteKind::Unknown)
+    }
+
+    // contiguous ranges of sprite data, [start, end)
+    pub fn data_regions(&self) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        let mut start = None;
+
+        for (index, kind) in self.bytes.iter().enumerate().skip(self.entry as usize) {
+            match (kind, start) {
+                (ByteKind::Data, None) => start = Some(index),
+                (ByteKind::Data, Some(_)) => {}
+                (_, Some(begin)) => {
+                    regions.push((begin, index));
+                    start = None;
+                }
+                (_, None) => {}
+            }
+        }
+
+        if let Some(begin) = start {
+            regions.push((begin, self.bytes.len()));
+        }
+
+        regions
+    }
+
+    // export the control flow graph in graphviz dot format
+    pub fn to_dot(&self, memory: &[u8]) -> String {
+        let mut dot = String::new();
+
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=monospace];").unwrap();
+
+        for block in self.blocks.values() {
+            let mut label = format!("{:#05x}:\\l", block.start);
+
+            for addr in (block.start..block.end).step_by(2) {
+                let index = addr as usize;
+                let mnemonic = disassemble(memory[index], memory[index + 1]).unwrap_or_default();
+
+                write!(label, "{:#05x}  {}\\l", addr, mnemonic).unwrap();
+            }
+
+            let style = if block.start == self.entry {
+                ", style=bold"
+            } else if self.subroutines.contains(&block.start) {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+
+            writeln!(
+                dot,
+                "    b{:03x} [label=\"{}\"{}];",
+                block.start, label, style
+            )
+            .unwrap();
+        }
+
+        let data_regions = self.data_regions();
+
+        if !data_regions.is_empty() {
+            let mut label = "data:\\l".to_string();
+
+            for (start, end) in data_regions {
+                write!(label, "{:#05x}..{:#05x}\\l", start, end).unwrap();
+            }
+
+            writeln!(dot, "    data [shape=note, label=\"{}\"];", label).unwrap();
+        }
+
+        for block in self.blocks.values() {
+            for (kind, target) in block.edges.iter() {
+                let style = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [color=blue]",
+                    EdgeKind::Indirect => " [color=blue, style=dashed, label=\"+V0\"]",
+                    EdgeKind::Skip => " [color=red, label=\"skip\"]",
+                    EdgeKind::Call => " [style=dotted, label=\"call\"]",
+                };
+
+                writeln!(dot, "    b{:03x} -> b{:03x}{};", block.start, target, style).unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analysis, ByteKind, EdgeKind};
+
+    // rom loaded at 0x200 in otherwise empty memory
+    fn analyze(rom: &[u8]) -> Analysis {
+        let mut memory = vec![0; 0x1000];
+        memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+        Analysis::new(&memory, 0x200, 0x200 + rom.len())
+    }
+
+    // start, end and edges of every block
+    type Blocks = Vec<(u16, u16, Vec<(EdgeKind, u16)>)>;
+
+    fn block_edges(analysis: &Analysis) -> Blocks {
+        analysis
+            .blocks
+            .values()
+            .map(|block| (block.start, block.end, block.edges.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn blocks_end_at_branches_and_targets() {
+        // LD V0, 1; SE V0, 1; JP 0x208; LD V1, 2; EXIT
+        let analysis = analyze(&[0x60, 0x01, 0x30, 0x01, 0x12, 0x08, 0x61, 0x02, 0x00, 0xFD]);
+
+        assert_eq!(
+            block_edges(&analysis),
+            [
+                (
+                    0x200,
+                    0x204,
+                    vec![(EdgeKind::Fallthrough, 0x204), (EdgeKind::Skip, 0x206)]
+                ),
+                (0x204, 0x206, vec![(EdgeKind::Jump, 0x208)]),
+                (0x206, 0x208, vec![(EdgeKind::Fallthrough, 0x208)]),
+                (0x208, 0x20A, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_skip_can_skip() {
+        for (upper, lower) in [
+            (0x30, 0x00),
+            (0x40, 0x00),
+            (0x50, 0x10),
+            (0x90, 0x10),
+            (0xE0, 0x9E),
+            (0xE0, 0xA1),
+        ] {
+            assert_eq!(
+                Analysis::successors(0x200, upper, lower),
+                [(EdgeKind::Fallthrough, 0x202), (EdgeKind::Skip, 0x204)],
+                "{:02X}{:02X}",
+                upper,
+                lower
+            );
+        }
+
+        // an opcode that cant be decoded has no block
+        let analysis = analyze(&[0xFF, 0xFF]);
+        assert!(analysis.blocks.is_empty());
+    }
+
+    #[test]
+    fn calls_find_subroutines() {
+        // CALL 0x206; JP 0x202; padding; RET
+        let analysis = analyze(&[0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x00, 0xEE]);
+
+        assert!(analysis.subroutines.iter().eq(&[0x206]));
+        assert_eq!(
+            block_edges(&analysis),
+            [
+                (
+                    0x200,
+                    0x202,
+                    vec![(EdgeKind::Call, 0x206), (EdgeKind::Fallthrough, 0x202)]
+                ),
+                (0x202, 0x204, vec![(EdgeKind::Jump, 0x202)]),
+                (0x206, 0x208, vec![]),
+            ]
+        );
+        assert_eq!(analysis.byte_kind(0x204), ByteKind::Unknown);
+    }
+
+    #[test]
+    fn sprite_data_stops_at_instructions() {
+        // LD I, 0x206; DRW V0, V1, 5; JP 0x20E; sprite; padding; an opcode that cant be decoded
+        let analysis = analyze(&[
+            0xA2, 0x06, 0xD0, 0x15, 0x12, 0x0E, //
+            0xF0, 0x90, 0x90, 0x90, 0xF0, 0x00, //
+            0x00, 0x00, 0xFF, 0xFF, //
+        ]);
+
+        assert_eq!(analysis.byte_kind(0x204), ByteKind::Code);
+        assert_eq!(analysis.data_regions(), [(0x206, 0x20E)]);
+
+        // a jump target is not sprite data even if it cant be followed
+        assert_eq!(analysis.byte_kind(0x20E), ByteKind::Unknown);
+
+        let dot = analysis.to_dot(&[0; 0x1000]);
+        assert!(dot.contains("0x206..0x20e"), "{}", dot);
+    }
+}
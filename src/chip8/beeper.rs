@@ -1,5 +1,7 @@
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 
+use super::frontend::Audio;
+
 const SAMPLE_RATE: i32 = 44100;
 
 pub struct Callback {
@@ -53,3 +55,13 @@ impl Beeper {
         })
     }
 }
+
+impl Audio for Beeper {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use super::{
+    options::Config,
+    settings::Settings,
+    utils::{config_dir, Color},
+};
+
+const DATABASE_DIR: &str = "database"; // imported copy of the database, in the config directory
+const PROGRAMS_FILE: &str = "programs.json";
+const PLATFORMS_FILE: &str = "platforms.json";
+
+// what the community chip-8-database knows about a rom
+#[derive(Clone, Default)]
+pub struct RomInfo {
+    pub title: String,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+
+    pub platform: Option<String>, // platform id, eg. originalChip8 or superchip
+    pub config: Option<Config>,   // quirks of the platform with the fixes of this rom
+    pub tickrate: Option<u32>,    // instructions per frame
+    pub colors: Option<(Color, Color)>, // background, foreground
+    pub keys: Vec<(String, u8)>,  // what the game uses a key for, eg. ("up", 5)
+}
+
+impl RomInfo {
+    // the quirks, speed and palette of the rom on top of settings
+    pub fn apply(&self, mut settings: Settings) -> Settings {
+        if let Some(config) = self.config {
+            settings.config = config;
+        }
+
+        if let Some(tickrate) = self.tickrate {
+            settings.cycles_per_frame = tickrate;
+        }
+
+        if let Some((color_off, color_on)) = self.colors {
+            settings.color_off = color_off;
+            settings.color_on = color_on;
+        }
+
+        settings
+    }
+}
+
+// roms of the chip-8-database keyed by sha-1, see github.com/chip-8/chip-8-database
+#[derive(Default)]
+pub struct Database {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl Database {
+    // the imported copy in the user config directory, empty if nothing was imported yet
+    pub fn load() -> Result<Self, String> {
+        match Self::dir() {
+            Some(dir) if dir.join(PROGRAMS_FILE).exists() => Self::load_from(dir),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    // copies programs.json and platforms.json next to it into the config directory
+    pub fn import(programs_path: impl AsRef<Path>) -> Result<Self, String> {
+        let programs_path = programs_path.as_ref();
+        let source = programs_path.parent().unwrap_or_else(|| Path::new("."));
+
+        // check it before replacing the previous copy
+        let programs = read(programs_path)?;
+        let platforms = read(source.join(PLATFORMS_FILE)).ok();
+
+        let database = Self::parse(&programs, platforms.as_deref())?;
+
+        let dir = Self::dir().ok_or("cannot find the user config directory")?;
+        fs::create_dir_all(&dir).map_err(|err| format!("{}\npath: {:?}", err, dir))?;
+
+        write(dir.join(PROGRAMS_FILE), &programs)?;
+
+        match platforms {
+            Some(platforms) => write(dir.join(PLATFORMS_FILE), &platforms)?,
+            None => {
+                let _ = fs::remove_file(dir.join(PLATFORMS_FILE));
+            }
+        }
+
+        Ok(database)
+    }
+
+    // programs.json and, if it is there, platforms.json in dir
+    pub fn load_from(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let programs = read(dir.as_ref().join(PROGRAMS_FILE))?;
+        let platforms = read(dir.as_ref().join(PLATFORMS_FILE)).ok();
+
+        Self::parse(&programs, platforms.as_deref())
+    }
+
+    // without platforms.json the quirks of the well known platforms are used
+    pub fn parse(programs: &str, platforms: Option<&str>) -> Result<Self, String> {
+        let programs: Value = serde_json::from_str(programs)
+            .map_err(|err| format!("invalid rom database\n{}", err))?;
+
+        let programs = programs
+            .as_array()
+            .ok_or("invalid rom database\nexpected an array of programs")?;
+
+        let platforms = match platforms {
+            Some(platforms) => parse_platforms(platforms)?,
+            None => HashMap::new(),
+        };
+
+        let mut database = Self::default();
+
+        for program in programs.iter() {
+            let text = |key| program.get(key).and_then(Value::as_str).map(str::to_string);
+
+            let info = RomInfo {
+                title: text("title").unwrap_or_default(),
+                description: text("description"),
+                authors: program
+                    .get("authors")
+                    .and_then(Value::as_array)
+                    .map(|authors| {
+                        authors
+                            .iter()
+                            .filter_map(|author| author.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                release: text("release"),
+                ..RomInfo::default()
+            };
+
+            let roms = match program.get("roms").and_then(Value::as_object) {
+                Some(roms) => roms,
+                None => continue,
+            };
+
+            for (hash, rom) in roms.iter() {
+                let mut info = info.clone();
+
+                // the first platform is the one the rom was made for
+                info.platform = rom
+                    .get("platforms")
+                    .and_then(Value::as_array)
+                    .and_then(|platforms| platforms.first())
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+
+                if let Some(platform) = info.platform.as_ref() {
+                    let (quirks, tickrate) = match platforms.get(platform) {
+                        Some((quirks, tickrate)) => (Some(*quirks), *tickrate),
+                        None => (platform_quirks(platform), None),
+                    };
+
+                    // some roms need a fix on top of their platform
+                    let fixes = rom
+                        .get("quirkyPlatforms")
+                        .and_then(|quirky| quirky.get(platform.as_str()));
+
+                    info.config = match (quirks, fixes) {
+                        (Some(quirks), Some(fixes)) => Some(apply_quirks(quirks, fixes)),
+                        (Some(quirks), None) => Some(quirks),
+                        (None, Some(fixes)) => Some(apply_quirks(Config::chip8(), fixes)),
+                        (None, None) => None,
+                    };
+
+                    info.tickrate = tickrate;
+                }
+
+                if let Some(tickrate) = rom.get("tickrate").and_then(Value::as_u64) {
+                    info.tickrate = Some(tickrate as u32);
+                }
+
+                info.colors = rom
+                    .get("colors")
+                    .and_then(|colors| colors.get("pixels"))
+                    .and_then(Value::as_array)
+                    .and_then(|pixels| {
+                        let color = |index: usize| {
+                            pixels
+                                .get(index)
+                                .and_then(Value::as_str)
+                                .and_then(Color::from_hex)
+                        };
+
+                        Some((color(0)?, color(1)?))
+                    });
+
+                if let Some(keys) = rom.get("keys").and_then(Value::as_object) {
+                    info.keys = keys
+                        .iter()
+                        .filter_map(|(name, key)| Some((name.clone(), key.as_u64()? as u8)))
+                        .collect();
+
+                    // sorted by key so the hints read like the keypad
+                    info.keys.sort_by_key(|(_, key)| *key);
+                }
+
+                database.roms.insert(hash.to_lowercase(), info);
+            }
+        }
+
+        Ok(database)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&RomInfo> {
+        self.roms.get(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    fn dir() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(DATABASE_DIR))
+    }
+}
+
+// platform id -> quirks and default tickrate
+fn parse_platforms(text: &str) -> Result<HashMap<String, (Config, Option<u32>)>, String> {
+    let platforms: Value =
+        serde_json::from_str(text).map_err(|err| format!("invalid platform database\n{}", err))?;
+
+    let platforms = platforms
+        .as_array()
+        .ok_or("invalid platform database\nexpected an array of platforms")?;
+
+    let mut result = HashMap::new();
+
+    for platform in platforms.iter() {
+        let id = match platform.get("id").and_then(Value::as_str) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let base = platform_quirks(id).unwrap_or_else(Config::chip8);
+
+        let quirks = match platform.get("quirks") {
+            Some(quirks) => apply_quirks(base, quirks),
+            None => base,
+        };
+
+        let tickrate = platform
+            .get("defaultTickrate")
+            .and_then(Value::as_u64)
+            .map(|tickrate| tickrate as u32);
+
+        result.insert(id.to_string(), (quirks, tickrate));
+    }
+
+    Ok(result)
+}
+
+// quirks the database names that the interpreter has, the rest are ignored
+fn apply_quirks(mut config: Config, quirks: &Value) -> Config {
+    let quirk = |name| quirks.get(name).and_then(Value::as_bool);
+
+    // the database describes the deviations from the original interpreter
+    if let Some(shift) = quirk("shift") {
+        config.shift_behaviour = !shift;
+    }
+
+    if let Some(wrap) = quirk("wrap") {
+        config.draw_behaviour = wrap;
+    }
+
+    if let Some(leave_i_unchanged) = quirk("memoryLeaveIUnchanged") {
+        config.store_behaviour = !leave_i_unchanged;
+    }
+
+    config
+}
+
+// quirks of the platforms in platforms.json, used when it wasnt imported
+fn platform_quirks(id: &str) -> Option<Config> {
+    let clip = Config {
+        draw_behaviour: false,
+        ..Config::chip8()
+    };
+
+    match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" => Some(clip),
+        "chip48" => Some(Config {
+            shift_behaviour: false,
+            ..clip
+        }),
+        "superchip1" | "superchip" => Some(Config::super_chip()),
+        "xochip" => Some(Config::chip8()),
+        _ => None,
+    }
+}
+
+fn read(path: impl AsRef<Path>) -> Result<String, String> {
+    fs::read_to_string(path.as_ref()).map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+}
+
+fn write(path: impl AsRef<Path>, text: &str) -> Result<(), String> {
+    fs::write(path.as_ref(), text).map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+}
@@ -0,0 +1,85 @@
+use super::symbols::SymbolTable;
+
+// turns a single opcode into a human readable mnemonic, returns None on unknown opcodes
+// opcodes marked with * are SuperChip instructions
+pub fn disassemble(upper: u8, lower: u8) -> Option<String> {
+    let x = upper & 0xF;
+    let y = lower >> 4;
+    let n = lower & 0xF;
+
+    let addr = (((upper & 0xF) as u16) << 8) | lower as u16;
+
+    let mnemonic = match upper >> 4 {
+        0x0 => match lower {
+            0xE0 => "CLS".to_string(),
+            0xEE => "RET".to_string(),
+            0xFB => "SCR".to_string(),                // 00FB*
+            0xFC => "SCL".to_string(),                // 00FC*
+            0xFD => "EXIT".to_string(),               // 00FD*
+            0xFE => "LOW".to_string(),                // 00FE*
+            0xFF => "HIGH".to_string(),               // 00FF*
+            _ if y == 0xC => format!("SCD {:#x}", n), // 00CN*
+            _ => return None,
+        },
+        0x1 => format!("JP {:#05x}", addr),
+        0x2 => format!("CALL {:#05x}", addr),
+        0x3 => format!("SE V{:X}, {:#04x}", x, lower),
+        0x4 => format!("SNE V{:X}, {:#04x}", x, lower),
+        0x5 => format!("SE V{:X}, V{:X}", x, y),
+        0x6 => format!("LD V{:X}, {:#04x}", x, lower),
+        0x7 => format!("ADD V{:X}, {:#04x}", x, lower),
+        0x8 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => return None,
+        },
+        0x9 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, {:#05x}", addr),
+        0xB => format!("JP V0, {:#05x}", addr),
+        0xC => format!("RND V{:X}, {:#04x}", x, lower),
+        0xD => format!("DRW V{:X}, V{:X}, {:#x}", x, y, n),
+        0xE => match lower {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => return None,
+        },
+        0xF => match lower {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x), // FX30*
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0x75 => format!("LD R, V{:X}", x), // FX75*
+            0x85 => format!("LD V{:X}, R", x), // FX85*
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(mnemonic)
+}
+
+// same as disassemble but replaces addresses with their labels when possible
+pub fn disassemble_with_symbols(upper: u8, lower: u8, symbols: &SymbolTable) -> Option<String> {
+    let addr = (((upper & 0xF) as u16) << 8) | lower as u16;
+
+    match (upper >> 4, symbols.name(addr)) {
+        (0x1, Some(name)) => Some(format!("JP {}", name)),
+        (0x2, Some(name)) => Some(format!("CALL {}", name)),
+        (0xA, Some(name)) => Some(format!("LD I, {}", name)),
+        (0xB, Some(name)) => Some(format!("JP V0, {}", name)),
+        _ => disassemble(upper, lower),
+    }
+}
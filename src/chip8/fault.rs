@@ -0,0 +1,137 @@
+use std::fmt;
+
+// errors raised by the interpreter while executing an instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExecutionFault {
+    StackOverflow,
+    StackUnderflow,
+    UnknownOpcode(u16),
+    MemoryOutOfRange { address: usize, len: usize },
+    InvalidFontDigit(u8),
+}
+
+impl ExecutionFault {
+    pub fn kind(&self) -> FaultKind {
+        match self {
+            Self::StackOverflow => FaultKind::StackOverflow,
+            Self::StackUnderflow => FaultKind::StackUnderflow,
+            Self::UnknownOpcode(_) => FaultKind::UnknownOpcode,
+            Self::MemoryOutOfRange { .. } => FaultKind::MemoryOutOfRange,
+            Self::InvalidFontDigit(_) => FaultKind::InvalidFontDigit,
+        }
+    }
+}
+
+impl fmt::Display for ExecutionFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StackOverflow => write!(f, "cannot push the stack\nstack overflow"),
+            Self::StackUnderflow => write!(
+                f,
+                "cannot pop the stack\ntried to pop the stack before pushing it"
+            ),
+            Self::UnknownOpcode(opcode) => {
+                write!(f, "unknown instruction\nopcode = {:04X}", opcode)
+            }
+            Self::MemoryOutOfRange { address, len } => write!(
+                f,
+                "memory access out of range\naddress = {:#x}, length = {}",
+                address, len
+            ),
+            Self::InvalidFontDigit(digit) => {
+                write!(f, "invalid font digit\ndigit = {:#x}", digit)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultKind {
+    StackOverflow,
+    StackUnderflow,
+    UnknownOpcode,
+    MemoryOutOfRange,
+    InvalidFontDigit,
+}
+
+impl FaultKind {
+    pub const ALL: [FaultKind; 5] = [
+        FaultKind::StackOverflow,
+        FaultKind::StackUnderflow,
+        FaultKind::UnknownOpcode,
+        FaultKind::MemoryOutOfRange,
+        FaultKind::InvalidFontDigit,
+    ];
+
+    // short name used by the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StackOverflow => "stack-overflow",
+            Self::StackUnderflow => "stack-underflow",
+            Self::UnknownOpcode => "unknown-opcode",
+            Self::MemoryOutOfRange => "memory",
+            Self::InvalidFontDigit => "font-digit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+// what the interpreter does when a fault is raised
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FaultPolicy {
+    Trap,   // stop before the faulting instruction and report the fault
+    Wrap,   // behave like real hardware, addresses and the stack pointer wrap around
+    Ignore, // skip the faulting instruction
+}
+
+impl FaultPolicy {
+    pub const ALL: [FaultPolicy; 3] = [FaultPolicy::Trap, FaultPolicy::Wrap, FaultPolicy::Ignore];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Trap => "trap",
+            Self::Wrap => "wrap",
+            Self::Ignore => "ignore",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == name)
+    }
+}
+
+// a policy for every kind of fault
+// faults without an address to wrap (unknown opcodes) are skipped by Wrap
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FaultPolicies {
+    policies: [FaultPolicy; FaultKind::ALL.len()],
+}
+
+impl FaultPolicies {
+    pub fn get(&self, kind: FaultKind) -> FaultPolicy {
+        self.policies[kind as usize]
+    }
+
+    pub fn get_mut(&mut self, kind: FaultKind) -> &mut FaultPolicy {
+        &mut self.policies[kind as usize]
+    }
+}
+
+impl Default for FaultPolicies {
+    fn default() -> Self {
+        let mut policies = Self {
+            policies: [FaultPolicy::Trap; FaultKind::ALL.len()],
+        };
+
+        // the font only has 16 digits, the original interpreter just uses the low nibble
+        *policies.get_mut(FaultKind::InvalidFontDigit) = FaultPolicy::Wrap;
+
+        policies
+    }
+}
@@ -0,0 +1,88 @@
+use super::machine::{Machine, S_HEIGHT, S_WIDTH};
+
+// games erase sprites by drawing them again with xor and then redraw them somewhere else,
+// so moving objects are off for every other frame. the filters run between the screen buffer
+// and the display and turn the lit pixels into a brightness per pixel
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Filter {
+    Off,
+    Blend,     // average of this and the previous frame
+    Phosphor,  // lit pixels fade out like a crt
+    Deflicker, // pixels are shown at once but cleared with a delay
+}
+
+impl Filter {
+    pub const ALL: [Filter; 4] = [
+        Filter::Off,
+        Filter::Blend,
+        Filter::Phosphor,
+        Filter::Deflicker,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::Off => "off",
+            Filter::Blend => "blend",
+            Filter::Phosphor => "phosphor",
+            Filter::Deflicker => "deflicker",
+        }
+    }
+}
+
+pub struct DisplayFilter {
+    pub filter: Filter,
+    pub half_life: f32, // frames until a cleared pixel fades to half brightness, phosphor
+    pub clear_delay: u32, // frames a cleared pixel stays lit, deflicker
+
+    brightness: Box<[f32; S_WIDTH * S_HEIGHT]>, // last output, 0 is off and 1 is fully lit
+    previous: Box<[bool; S_WIDTH * S_HEIGHT]>,  // lit pixels of the previous frame
+    off_frames: Box<[u32; S_WIDTH * S_HEIGHT]>, // frames since every pixel was cleared
+}
+
+impl Default for DisplayFilter {
+    fn default() -> Self {
+        Self {
+            filter: Filter::Off,
+            half_life: 2.0,
+            clear_delay: 2,
+
+            brightness: Box::new([0.0; S_WIDTH * S_HEIGHT]),
+            previous: Box::new([false; S_WIDTH * S_HEIGHT]),
+            off_frames: Box::new([u32::MAX; S_WIDTH * S_HEIGHT]),
+        }
+    }
+}
+
+impl DisplayFilter {
+    // brightness of every screen buffer pixel for the next frame, call once per frame
+    // the history of every filter is kept up to date so switching filters doesnt jump
+    pub fn apply(&mut self, machine: &Machine) -> &[f32] {
+        let decay = 0.5f32.powf(1.0 / self.half_life.max(0.01));
+
+        for (index, pixel) in machine.screen_buffer.iter().enumerate() {
+            let lit = *pixel != 0;
+
+            let off_frames = &mut self.off_frames[index];
+            *off_frames = if lit { 0 } else { off_frames.saturating_add(1) };
+
+            let brightness = &mut self.brightness[index];
+            *brightness = match self.filter {
+                Filter::Off => lit as u8 as f32,
+                Filter::Blend => (lit as u8 + self.previous[index] as u8) as f32 / 2.0,
+                Filter::Phosphor if lit => 1.0,
+                Filter::Phosphor => *brightness * decay,
+                Filter::Deflicker => (*off_frames <= self.clear_delay) as u8 as f32,
+            };
+
+            self.previous[index] = lit;
+        }
+
+        &*self.brightness
+    }
+
+    // output of the last apply, shown again until the next frame
+    pub fn brightness(&self) -> &[f32] {
+        &*self.brightness
+    }
+}
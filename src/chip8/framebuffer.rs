@@ -1,3 +1,8 @@
+use super::{
+    frontend::Display,
+    machine::{Machine, S_HEIGHT, S_WIDTH},
+    utils::Color,
+};
 use crate::gl_call;
 
 // simple struct for copying a texture to
@@ -5,6 +10,8 @@ use crate::gl_call;
 pub struct FrameBuffer {
     id: u32,
     texture: u32,
+
+    pixels: Box<[Color; S_WIDTH * S_HEIGHT]>, // rgba upload buffer
 }
 
 impl FrameBuffer {
@@ -19,7 +26,11 @@ impl FrameBuffer {
         gl_call!(gl::GenTextures(1, &mut texture));
         gl_call!(gl::BindTexture(gl::TEXTURE_2D, texture));
 
-        let framebuffer = Self { id, texture };
+        let framebuffer = Self {
+            id,
+            texture,
+            pixels: Box::new([Color::default(); S_WIDTH * S_HEIGHT]),
+        };
 
         gl_call!(gl::TexParameteri(
             gl::TEXTURE_2D,
@@ -81,6 +92,48 @@ impl FrameBuffer {
             gl::NEAREST
         ));
     }
+
+    // blends the background and foreground colors by the brightness of every pixel
+    pub fn present_filtered(&mut self, machine: &Machine, brightness: &[f32]) {
+        for (color, brightness) in self.pixels.iter_mut().zip(brightness.iter()) {
+            *color = machine.color_off.mix(machine.color_on, *brightness);
+        }
+
+        self.upload_pixels();
+    }
+
+    // random colors in the top left width x height pixels
+    pub fn present_noise(&mut self, width: usize, height: usize) {
+        for y in 0..height {
+            for x in 0..width {
+                self.pixels[y * S_WIDTH + x] = Color::rand();
+            }
+        }
+
+        self.upload_pixels();
+    }
+
+    fn upload_pixels(&self) {
+        self.update_buffer(
+            S_WIDTH as _,
+            S_HEIGHT as _,
+            self.pixels.as_ptr() as _,
+            gl::RGBA8,
+            gl::RGBA,
+        );
+    }
+}
+
+// uploads the whole screen buffer, draw_buffer picks the visible part
+// the palette is only applied here so color changes dont touch the machine
+impl Display for FrameBuffer {
+    fn present(&mut self, machine: &Machine) {
+        for (color, pixel) in self.pixels.iter_mut().zip(machine.screen_buffer.iter()) {
+            *color = machine.color(*pixel);
+        }
+
+        self.upload_pixels();
+    }
 }
 
 impl Drop for FrameBuffer {
@@ -0,0 +1,160 @@
+use super::{fault::ExecutionFault, machine::Machine};
+
+// interfaces between the interpreter and a frontend
+// a frontend implements these and drives the machine with run_frame
+
+pub trait Display {
+    // called once per frame with the finished display
+    fn present(&mut self, machine: &Machine);
+}
+
+pub trait Audio {
+    // called once per frame, the tone plays while the sound timer is running
+    fn set_beeping(&mut self, beeping: bool);
+}
+
+pub trait Input {
+    // called once per frame before running instructions
+    fn update_keys(&mut self, keys: &mut [bool; 16]);
+}
+
+// closures work as displays, eg. for recording every frame
+impl<F: FnMut(&Machine)> Display for F {
+    fn present(&mut self, machine: &Machine) {
+        self(machine)
+    }
+}
+
+// a missing device, eg. no audio output, plays nothing
+impl<A: Audio> Audio for Option<A> {
+    fn set_beeping(&mut self, beeping: bool) {
+        if let Some(audio) = self {
+            audio.set_beeping(beeping);
+        }
+    }
+}
+
+// run one frame of instructions, elapsed is the frame time in seconds
+// a trapped fault stops the frame before the timers, audio and display are updated
+pub fn run_frame(
+    machine: &mut Machine,
+    cycles_per_frame: u32,
+    elapsed: f64,
+    input: &mut impl Input,
+    audio: &mut impl Audio,
+    display: &mut impl Display,
+) -> Result<(), ExecutionFault> {
+    run_frame_with(
+        machine,
+        cycles_per_frame,
+        elapsed,
+        input,
+        audio,
+        display,
+        |_| true,
+    )
+}
+
+// run_frame that calls before_step ahead of every instruction, eg. for breakpoints and tracing
+// returning false ends the instructions of this frame early, the rest of the frame still runs
+pub fn run_frame_with(
+    machine: &mut Machine,
+    cycles_per_frame: u32,
+    elapsed: f64,
+    input: &mut impl Input,
+    audio: &mut impl Audio,
+    display: &mut impl Display,
+    mut before_step: impl FnMut(&Machine) -> bool,
+) -> Result<(), ExecutionFault> {
+    input.update_keys(&mut machine.keys);
+    machine.polled_keys = [false; 16];
+
+    for _ in 0..cycles_per_frame {
+        if !machine.is_running() || !before_step(machine) {
+            break;
+        }
+
+        machine.step()?;
+    }
+
+    machine.update_timers(elapsed);
+
+    audio.set_beeping(machine.is_beeping());
+    display.present(machine);
+
+    Ok(())
+}
+
+// frontends that do nothing
+
+pub struct NullDisplay;
+
+impl Display for NullDisplay {
+    fn present(&mut self, _machine: &Machine) {}
+}
+
+pub struct NullAudio;
+
+impl Audio for NullAudio {
+    fn set_beeping(&mut self, _beeping: bool) {}
+}
+
+pub struct NullInput;
+
+impl Input for NullInput {
+    fn update_keys(&mut self, _keys: &mut [bool; 16]) {}
+}
+
+// frontends that keep everything in memory for tests
+
+#[derive(Default)]
+pub struct MemoryDisplay {
+    pub frames: u32,       // presented frames
+    pub pixels: Vec<bool>, // last presented display, width x height
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Display for MemoryDisplay {
+    fn present(&mut self, machine: &Machine) {
+        self.frames += 1;
+        self.width = machine.width;
+        self.height = machine.height;
+
+        self.pixels.clear();
+
+        for y in 0..machine.height {
+            for x in 0..machine.width {
+                self.pixels.push(machine.is_pixel_on(x, y));
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryAudio {
+    pub beeping: bool,
+    pub beeps: u32, // number of times the tone started
+}
+
+impl Audio for MemoryAudio {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping && !self.beeping {
+            self.beeps += 1;
+        }
+
+        self.beeping = beeping;
+    }
+}
+
+// keys set by the test, copied to the machine every frame
+#[derive(Default)]
+pub struct MemoryInput {
+    pub keys: [bool; 16],
+}
+
+impl Input for MemoryInput {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        *keys = self.keys;
+    }
+}
@@ -0,0 +1,102 @@
+use sdl2::{
+    controller::{Axis, Button, GameController},
+    event::Event,
+    GameControllerSubsystem, Sdl,
+};
+
+// how far a stick or trigger has to be pushed to press a key, out of 32767
+const STICK_THRESHOLD: i16 = 16384;
+
+// a button or a stick direction of a game controller
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PadInput {
+    Button(Button),
+    Axis(Axis, bool), // true for the positive direction, right or down
+}
+
+impl PadInput {
+    // sdl's names, eg. a, dpup, or leftx+ for the left stick pushed right
+    pub fn name(self) -> String {
+        match self {
+            PadInput::Button(button) => button.string(),
+            PadInput::Axis(axis, true) => format!("{}+", axis.string()),
+            PadInput::Axis(axis, false) => format!("{}-", axis.string()),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(axis) = name.strip_suffix('+') {
+            return Axis::from_string(axis).map(|axis| PadInput::Axis(axis, true));
+        }
+
+        if let Some(axis) = name.strip_suffix('-') {
+            return Axis::from_string(axis).map(|axis| PadInput::Axis(axis, false));
+        }
+
+        Button::from_string(name).map(PadInput::Button)
+    }
+}
+
+// the plugged in game controllers, opened and closed as they come and go
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    controllers: Vec<GameController>,
+}
+
+impl Gamepads {
+    // controllers plugged in before are reported by the first poll like new ones
+    pub fn new(sdl: &Sdl) -> Result<Self, String> {
+        Ok(Self {
+            subsystem: sdl.game_controller()?,
+            controllers: Vec::new(),
+        })
+    }
+
+    // returns the button or stick direction pressed by event, if any
+    pub fn handle_event(&mut self, event: &Event) -> Option<PadInput> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.open(which) {
+                    Ok(controller) => {
+                        let id = controller.instance_id();
+
+                        if self.controllers.iter().all(|open| open.instance_id() != id) {
+                            self.controllers.push(controller);
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("warning: cannot open game controller {}\n{}", which, err)
+                    }
+                }
+
+                None
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers
+                    .retain(|controller| controller.instance_id() != which);
+
+                None
+            }
+            Event::ControllerButtonDown { button, .. } => Some(PadInput::Button(button)),
+            Event::ControllerAxisMotion { axis, value, .. }
+                if value >= STICK_THRESHOLD || value <= -STICK_THRESHOLD =>
+            {
+                Some(PadInput::Axis(axis, value > 0))
+            }
+            _ => None,
+        }
+    }
+
+    // pressed on any of the controllers
+    pub fn is_pressed(&self, input: PadInput) -> bool {
+        self.controllers.iter().any(|controller| match input {
+            PadInput::Button(button) => controller.button(button),
+            PadInput::Axis(axis, true) => controller.axis(axis) >= STICK_THRESHOLD,
+            PadInput::Axis(axis, false) => controller.axis(axis) <= -STICK_THRESHOLD,
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.controllers.iter().map(GameController::name)
+    }
+}
@@ -0,0 +1,246 @@
+use std::{fs, path::PathBuf};
+
+use serde_json::json;
+
+use super::{
+    analyzer::Analysis,
+    fault::ExecutionFault,
+    frontend::{self, Input, NullAudio},
+    image,
+    machine::Machine,
+    options::Options,
+    recorder::Recorder,
+};
+
+// exit codes of a headless run
+pub const EXIT_OK: i32 = 0; // every frame ran or the rom exited with 00FD
+pub const EXIT_FAULT: i32 = 1; // the rom raised a trapping fault
+
+pub struct Headless {
+    pub frames: u32,
+    pub input_path: Option<PathBuf>,  // scripted key presses
+    pub screen_path: Option<PathBuf>, // final display, "-" prints ascii art to stdout
+    pub state_path: Option<PathBuf>,  // final registers and memory as json
+    pub record_path: Option<PathBuf>, // every frame as gif or y4m, "-" writes y4m to stdout
+    pub cfg_path: Option<PathBuf>,    // control flow graph of the rom as graphviz dot
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Self {
+            frames: 600, // 10 seconds
+            input_path: None,
+            screen_path: None,
+            state_path: None,
+            record_path: None,
+            cfg_path: None,
+        }
+    }
+}
+
+// key states that change at given frames
+// one `frame keys` pair per line, keys are hex digits held from that frame on, "." releases all
+// eg.
+//   60 5    hold 5 at frame 60
+//   64 .    release it 4 frames later
+#[derive(Default)]
+pub struct InputScript {
+    events: Vec<(u32, [bool; 16])>,
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+
+            let frame = match words.next() {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            let invalid = || format!("invalid input script\nline {}", line_number + 1);
+
+            let frame = frame.parse::<u32>().map_err(|_| invalid())?;
+
+            let mut keys = [false; 16];
+
+            for key in words.next().unwrap_or(".").chars().filter(|c| *c != '.') {
+                let key = key.to_digit(16).ok_or_else(invalid)?;
+                keys[key as usize] = true;
+            }
+
+            events.push((frame, keys));
+        }
+
+        events.sort_by_key(|(frame, _)| *frame);
+
+        Ok(Self { events })
+    }
+
+    // key states that start at the given frame, None if they dont change
+    pub fn keys_at(&self, frame: u32) -> Option<[bool; 16]> {
+        self.events
+            .iter()
+            .rev()
+            .find(|(start, _)| *start == frame)
+            .map(|(_, keys)| *keys)
+    }
+}
+
+// run the rom without a window and dump the results, returns the exit code
+pub fn run(options: &Options, headless: &Headless) -> Result<i32, String> {
+    let rom_path = options
+        .rom_path
+        .as_ref()
+        .ok_or("headless mode requires a rom")?;
+
+    let is_stdout =
+        |path: &Option<PathBuf>| path.as_ref().and_then(|path| path.to_str()) == Some("-");
+
+    if is_stdout(&headless.screen_path) && is_stdout(&headless.record_path) {
+        return Err("cannot write both the screen and the recording to stdout".to_string());
+    }
+
+    let rom = fs::read(rom_path).map_err(|err| format!("{}\npath: {:?}", err, rom_path))?;
+
+    let script = match headless.input_path.as_ref() {
+        Some(path) => {
+            let text =
+                fs::read_to_string(path).map_err(|err| format!("{}\npath: {:?}", err, path))?;
+            InputScript::parse(&text)?
+        }
+        None => InputScript::default(),
+    };
+
+    let mut machine = Machine::new(options);
+    let end = machine.load_rom(&rom)?;
+
+    // the graph is taken from the rom as loaded, before it runs and maybe modifies itself
+    if let Some(path) = headless.cfg_path.as_ref() {
+        let dot = Analysis::new(&*machine.memory, 0x200, end).to_dot(&*machine.memory);
+
+        fs::write(path, dot).map_err(|err| format!("{}\npath: {:?}", err, path))?;
+    }
+
+    let mut recorder = match headless.record_path.as_ref() {
+        Some(path) => Some(Recorder::create(path, &machine, 1)?),
+        None => None,
+    };
+
+    // keep recording errors until the run is over, the recorder is finished either way
+    let mut record_result = Ok(());
+
+    let (frames, fault) = run_frames(
+        &mut machine,
+        options.cycles_per_frame,
+        headless.frames,
+        &script,
+        |machine| {
+            if let (Some(recorder), Ok(())) = (recorder.as_mut(), record_result.as_ref()) {
+                record_result = recorder.capture(machine);
+            }
+        },
+    );
+
+    record_result?;
+
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+
+    if let Some(path) = headless.screen_path.as_ref() {
+        if path.to_str() == Some("-") {
+            print!("{}", image::to_ascii(&machine));
+        } else {
+            image::write_screen(path, &machine)?;
+        }
+    }
+
+    if let Some(path) = headless.state_path.as_ref() {
+        let state = state_json(&machine, frames, fault);
+
+        fs::write(path, state.to_string()).map_err(|err| format!("{}\npath: {:?}", err, path))?;
+    }
+
+    match fault {
+        Some(fault) => {
+            eprintln!("{}\npc = {:#x}", fault, machine.pc);
+            Ok(EXIT_FAULT)
+        }
+        None => Ok(EXIT_OK),
+    }
+}
+
+// plays back an input script, one frame per update
+struct ScriptedInput<'a> {
+    script: &'a InputScript,
+    frame: u32,
+}
+
+impl Input for ScriptedInput<'_> {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        if let Some(script_keys) = self.script.keys_at(self.frame) {
+            *keys = script_keys;
+        }
+
+        self.frame += 1;
+    }
+}
+
+// runs until the given frame count, until the rom exits or until a fault is trapped
+// on_frame is called with the display at the end of every frame
+// returns the number of frames ran and the trapped fault
+pub fn run_frames(
+    machine: &mut Machine,
+    cycles_per_frame: u32,
+    frames: u32,
+    script: &InputScript,
+    mut on_frame: impl FnMut(&Machine),
+) -> (u32, Option<ExecutionFault>) {
+    let mut input = ScriptedInput { script, frame: 0 };
+
+    for frame in 0..frames {
+        if let Err(fault) = frontend::run_frame(
+            machine,
+            cycles_per_frame,
+            1.0 / 60.0,
+            &mut input,
+            &mut NullAudio,
+            &mut on_frame,
+        ) {
+            on_frame(machine);
+            return (frame + 1, Some(fault));
+        }
+
+        if !machine.is_running() {
+            return (frame + 1, None);
+        }
+    }
+
+    (frames, None)
+}
+
+pub fn state_json(
+    machine: &Machine,
+    frames: u32,
+    fault: Option<ExecutionFault>,
+) -> serde_json::Value {
+    json!({
+        "frames": frames,
+        "error": fault.map(|fault| fault.to_string()),
+        "pc": machine.pc,
+        "sp": machine.sp,
+        "i": machine.r_address,
+        "v": machine.v,
+        "flag_registers": machine.flag_registers,
+        "stack": machine.stack,
+        "delay_timer": machine.r_delay_timer,
+        "sound_timer": machine.r_sound_timer,
+        "width": machine.width,
+        "height": machine.height,
+        "memory": &machine.memory[..],
+    })
+}
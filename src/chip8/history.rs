@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+
+// register state before an instruction, small enough to copy on every step
+#[derive(Clone, Copy)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub flag_registers: [u8; 8],
+    pub stack: [u16; 16],
+    pub sp: usize,
+    pub pc: u16,
+    pub r_address: u16,
+    pub r_delay_timer: u8,
+    pub r_sound_timer: u8,
+    pub delay_tick: f64,
+    pub sound_tick: f64,
+    pub rng_state: u64,
+    pub width: usize,
+    pub height: usize,
+    pub waiting_key_input: bool,
+    pub rom_loaded: bool,
+}
+
+// a value overwritten by an instruction
+#[derive(Clone, Copy)]
+pub enum Change {
+    Memory(usize, u8), // address, previous byte
+    Pixel(usize, u8),  // screen buffer index, previous planes
+}
+
+// an executed instruction and everything needed to undo it
+pub struct Entry {
+    pub opcode: u16,
+    pub registers: Registers, // registers.pc is the address of the instruction
+    pub changes: Vec<Change>,
+}
+
+// ring buffer of the last executed instructions
+// the timer ticks and the random number generator are kept with the registers
+pub struct History {
+    entries: VecDeque<Entry>,
+    capacity: usize, // 0 disables recording
+
+    recording: Option<Entry>, // instruction that is currently executing
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+
+            recording: None,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // drops the oldest entries if the history doesnt fit anymore
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+
+        self.capacity = capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // oldest entry first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub(super) fn begin(&mut self, opcode: u16, registers: Registers) {
+        if self.capacity > 0 {
+            self.recording = Some(Entry {
+                opcode,
+                registers,
+                changes: Vec::new(),
+            });
+        }
+    }
+
+    pub(super) fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub(super) fn record(&mut self, change: Change) {
+        if let Some(entry) = self.recording.as_mut() {
+            entry.changes.push(change);
+        }
+    }
+
+    pub(super) fn commit(&mut self) {
+        if let Some(entry) = self.recording.take() {
+            if self.entries.len() >= self.capacity {
+                self.entries.pop_front();
+            }
+
+            self.entries.push_back(entry);
+        }
+    }
+
+    pub(super) fn discard(&mut self) {
+        self.recording = None;
+    }
+
+    pub(super) fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_back()
+    }
+}
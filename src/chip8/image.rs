@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use super::machine::Machine;
+
+// rgb pixels of the visible part of the screen, every pixel is scaled to a scale x scale square
+pub fn screen_rgb(machine: &Machine, scale: usize) -> (usize, usize, Vec<u8>) {
+    let width = machine.width * scale;
+    let height = machine.height * scale;
+
+    let mut data = Vec::with_capacity(width * height * 3);
+
+    for y in 0..height {
+        for x in 0..width {
+            data.extend_from_slice(&machine.pixel_color(x / scale, y / scale).as_rgb());
+        }
+    }
+
+    (width, height, data)
+}
+
+pub fn write_png(path: impl AsRef<Path>, machine: &Machine, scale: usize) -> Result<(), String> {
+    let (width, height, data) = screen_rgb(machine, scale);
+
+    let file =
+        File::create(path.as_ref()).map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|err| format!("cannot write png\n{}", err))
+}
+
+// first unused <rom name>-<n>.<extension> in the working directory
+pub fn output_path(rom_path: &Path, extension: &str) -> PathBuf {
+    let name = rom_path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("chip8");
+
+    (1..)
+        .map(|n| PathBuf::from(format!("{}-{}.{}", name, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+// plain portable bitmap, 1 is a lit pixel
+pub fn to_pbm(machine: &Machine) -> String {
+    let mut pbm = format!("P1\n{} {}\n", machine.width, machine.height);
+
+    for y in 0..machine.height {
+        let row: Vec<&str> = (0..machine.width)
+            .map(|x| if machine.is_pixel_on(x, y) { "1" } else { "0" })
+            .collect();
+
+        pbm.push_str(&row.join(" "));
+        pbm.push('\n');
+    }
+
+    pbm
+}
+
+pub fn to_ascii(machine: &Machine) -> String {
+    let mut ascii = String::with_capacity((machine.width + 1) * machine.height);
+
+    for y in 0..machine.height {
+        for x in 0..machine.width {
+            ascii.push(if machine.is_pixel_on(x, y) { '#' } else { '.' });
+        }
+        ascii.push('\n');
+    }
+
+    ascii
+}
+
+// picks the format from the file extension: png, pbm or anything else for ascii art
+pub fn write_screen(path: impl AsRef<Path>, machine: &Machine) -> Result<(), String> {
+    let path = path.as_ref();
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    let text = match extension.as_deref() {
+        Some("png") => return write_png(path, machine, 1),
+        Some("pbm") => to_pbm(machine),
+        _ => to_ascii(machine),
+    };
+
+    fs::write(path, text).map_err(|err| format!("{}\npath: {:?}", err, path))
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{
+    controller::{Axis, Button},
+    keyboard::{KeyboardState, Scancode},
+    EventPump,
+};
+
+use super::{
+    frontend::Input,
+    gamepad::{Gamepads, PadInput},
+    utils::config_dir,
+};
+
+const KEYS_FILE: &str = "keys.toml";
+
+// names of the chip8 keys in the files
+const KEY_NAMES: [&str; 16] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F",
+];
+
+// 1234 qwer asdf zxcv, the keypad layout on the left of the keyboard
+const KEYPAD: [Scancode; 16] = [
+    Scancode::X,
+    Scancode::Num1,
+    Scancode::Num2,
+    Scancode::Num3,
+    Scancode::Q,
+    Scancode::W,
+    Scancode::E,
+    Scancode::A,
+    Scancode::S,
+    Scancode::D,
+    Scancode::Z,
+    Scancode::C,
+    Scancode::Num4,
+    Scancode::R,
+    Scancode::F,
+    Scancode::V,
+];
+
+// 789/ 456* 123- 0.+ and enter, the keypad layout on the number pad
+const NUMPAD: [Scancode; 16] = [
+    Scancode::KpPeriod,
+    Scancode::Kp7,
+    Scancode::Kp8,
+    Scancode::Kp9,
+    Scancode::Kp4,
+    Scancode::Kp5,
+    Scancode::Kp6,
+    Scancode::Kp1,
+    Scancode::Kp2,
+    Scancode::Kp3,
+    Scancode::Kp0,
+    Scancode::KpEnter,
+    Scancode::KpDivide,
+    Scancode::KpMultiply,
+    Scancode::KpMinus,
+    Scancode::KpPlus,
+];
+
+// d-pad and left stick as 2/4/6/8 and a as 5, like the libretro core
+const PAD: [(usize, PadInput); 9] = [
+    (0x2, PadInput::Button(Button::DPadUp)),
+    (0x4, PadInput::Button(Button::DPadLeft)),
+    (0x6, PadInput::Button(Button::DPadRight)),
+    (0x8, PadInput::Button(Button::DPadDown)),
+    (0x2, PadInput::Axis(Axis::LeftY, false)),
+    (0x4, PadInput::Axis(Axis::LeftX, false)),
+    (0x6, PadInput::Axis(Axis::LeftX, true)),
+    (0x8, PadInput::Axis(Axis::LeftY, true)),
+    (0x5, PadInput::Button(Button::A)),
+];
+
+// keyboard keys and controller inputs of every chip8 key, each belongs to at most one chip8 key
+#[derive(Clone, PartialEq)]
+pub struct KeyMap {
+    keys: [Vec<Scancode>; 16],
+    pad: [Vec<PadInput>; 16],
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::from_scancodes(&KEYPAD)
+    }
+}
+
+impl KeyMap {
+    pub const LAYOUTS: [&'static str; 2] = ["keypad", "numpad"];
+
+    // built in layouts
+    pub fn layout(name: &str) -> Option<Self> {
+        match name {
+            "keypad" => Some(Self::from_scancodes(&KEYPAD)),
+            "numpad" => Some(Self::from_scancodes(&NUMPAD)),
+            _ => None,
+        }
+    }
+
+    fn from_scancodes(scancodes: &[Scancode; 16]) -> Self {
+        let mut map = Self {
+            keys: Default::default(),
+            pad: Self::default_pad(),
+        };
+
+        for (key, scancode) in scancodes.iter().enumerate() {
+            map.keys[key].push(*scancode);
+        }
+
+        map
+    }
+
+    fn default_pad() -> [Vec<PadInput>; 16] {
+        let mut pad: [Vec<PadInput>; 16] = Default::default();
+
+        for (key, input) in PAD.iter() {
+            pad[*key].push(*input);
+        }
+
+        pad
+    }
+
+    pub fn keys(&self, key: usize) -> &[Scancode] {
+        &self.keys[key]
+    }
+
+    // adds scancode to key and takes it away from any other key
+    pub fn bind(&mut self, key: usize, scancode: Scancode) {
+        for keys in self.keys.iter_mut() {
+            keys.retain(|bound| *bound != scancode);
+        }
+
+        self.keys[key].push(scancode);
+    }
+
+    pub fn unbind(&mut self, key: usize, scancode: Scancode) {
+        self.keys[key].retain(|bound| *bound != scancode);
+    }
+
+    pub fn pad(&self, key: usize) -> &[PadInput] {
+        &self.pad[key]
+    }
+
+    // adds input to key and takes it away from any other key
+    pub fn bind_pad(&mut self, key: usize, input: PadInput) {
+        for pad in self.pad.iter_mut() {
+            pad.retain(|bound| *bound != input);
+        }
+
+        self.pad[key].push(input);
+    }
+
+    pub fn unbind_pad(&mut self, key: usize, input: PadInput) {
+        self.pad[key].retain(|bound| *bound != input);
+    }
+
+    pub fn update_keys(
+        &self,
+        keyboard_state: &KeyboardState,
+        gamepads: Option<&Gamepads>,
+        keys: &mut [bool; 16],
+    ) {
+        for (key, pressed) in keys.iter_mut().enumerate() {
+            let on_keyboard = self.keys[key]
+                .iter()
+                .any(|scancode| keyboard_state.is_scancode_pressed(*scancode));
+
+            let on_pad = match gamepads {
+                Some(gamepads) => self.pad[key]
+                    .iter()
+                    .any(|input| gamepads.is_pressed(*input)),
+                None => false,
+            };
+
+            *pressed = on_keyboard || on_pad;
+        }
+    }
+
+    // the chip8 key typed by c, for terminals that report characters instead of scancodes
+    // the main keyboard and the numpad give the same digits, so either binding counts
+    pub fn key_for_char(&self, c: char) -> Option<usize> {
+        let scancodes = [
+            Scancode::from_name(&c.to_string()),
+            Scancode::from_name(&format!("Keypad {}", c)),
+        ];
+
+        self.keys.iter().position(|keys| {
+            scancodes
+                .iter()
+                .flatten()
+                .any(|scancode| keys.contains(scancode))
+        })
+    }
+
+    // `0 = ["X"]` for every key and the same for the controller in a pad table,
+    // keys without bindings are left out
+    pub fn to_table(&self) -> toml::value::Table {
+        let mut table = names_table(&self.keys, |scancode| scancode.name().to_string());
+
+        table.insert(
+            "pad".to_string(),
+            toml::Value::Table(names_table(&self.pad, |input| input.name())),
+        );
+
+        table
+    }
+
+    // the default controller bindings are kept if there is no pad table
+    pub fn from_table(table: &toml::value::Table) -> Result<Self, String> {
+        let mut map = Self {
+            keys: Default::default(),
+            pad: Self::default_pad(),
+        };
+
+        if let Some(pad) = table.get("pad") {
+            let pad = pad
+                .as_table()
+                .ok_or_else(|| format!("invalid controller bindings {}", pad))?;
+
+            map.pad = Default::default();
+
+            for (key, input) in parse_names(pad, PadInput::from_name)? {
+                map.bind_pad(key, input);
+            }
+        }
+
+        let mut keys = table.clone();
+        keys.remove("pad");
+
+        for (key, scancode) in parse_names(&keys, Scancode::from_name)? {
+            map.bind(key, scancode);
+        }
+
+        Ok(map)
+    }
+}
+
+// chip8 key name -> names of what is bound to it
+fn names_table<T: Copy>(bindings: &[Vec<T>; 16], name: impl Fn(T) -> String) -> toml::value::Table {
+    let mut table = toml::value::Table::new();
+
+    for (key, bound) in KEY_NAMES.iter().zip(bindings.iter()) {
+        if bound.is_empty() {
+            continue;
+        }
+
+        let names = bound
+            .iter()
+            .map(|binding| toml::Value::String(name(*binding)))
+            .collect();
+
+        table.insert(key.to_string(), toml::Value::Array(names));
+    }
+
+    table
+}
+
+// the chip8 keys and what is bound to them, in the order of the file
+fn parse_names<T>(
+    table: &toml::value::Table,
+    from_name: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<(usize, T)>, String> {
+    let mut bindings = Vec::new();
+
+    for (name, value) in table.iter() {
+        let key = KEY_NAMES
+            .iter()
+            .position(|key| key.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown chip8 key {:?}", name))?;
+
+        let invalid = || format!("invalid keys {} for {}", value, name);
+
+        for binding in value.as_array().ok_or_else(invalid)?.iter() {
+            let binding = binding.as_str().and_then(&from_name).ok_or_else(invalid)?;
+
+            bindings.push((key, binding));
+        }
+    }
+
+    Ok(bindings)
+}
+
+// named key maps and the one in use, kept in keys.toml in the config directory
+pub struct Bindings {
+    active: String,
+    layouts: BTreeMap<String, KeyMap>, // always has the built in layouts and the active one
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let layouts = KeyMap::LAYOUTS
+            .iter()
+            .map(|name| (name.to_string(), KeyMap::layout(name).unwrap()))
+            .collect();
+
+        Self {
+            active: KeyMap::LAYOUTS[0].to_string(),
+            layouts,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(KEYS_FILE))
+    }
+
+    pub fn load() -> Result<Self, String> {
+        match Self::path() {
+            Some(path) => Self::load_from(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("cannot find the user config directory")?;
+
+        self.save_to(path)
+    }
+
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, String> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(text) => {
+                Self::from_toml(&text).map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("{}\npath: {:?}", err, path.as_ref())),
+        }
+    }
+
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_toml())
+            .map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let value: toml::Value = text
+            .parse()
+            .map_err(|err| format!("invalid key bindings file\n{}", err))?;
+
+        let mut bindings = Self::default();
+
+        if let Some(layouts) = value.get("layouts") {
+            let layouts = layouts
+                .as_table()
+                .ok_or("invalid key bindings file\nlayouts must be a table")?;
+
+            for (name, layout) in layouts.iter() {
+                let layout = layout
+                    .as_table()
+                    .ok_or_else(|| format!("invalid key bindings file\ninvalid layout {}", name))?;
+
+                let map = KeyMap::from_table(layout)
+                    .map_err(|err| format!("invalid key bindings file\n{}", err))?;
+
+                bindings.layouts.insert(name.clone(), map);
+            }
+        }
+
+        if let Some(active) = value.get("active").and_then(toml::Value::as_str) {
+            bindings.select(active);
+        }
+
+        Ok(bindings)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let layouts = self
+            .layouts
+            .iter()
+            .map(|(name, map)| (name.clone(), toml::Value::Table(map.to_table())))
+            .collect();
+
+        let mut table = toml::value::Table::new();
+        table.insert(
+            "active".to_string(),
+            toml::Value::String(self.active.clone()),
+        );
+        table.insert("layouts".to_string(), toml::Value::Table(layouts));
+
+        toml::to_string(&toml::Value::Table(table)).unwrap_or_default()
+    }
+
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layouts.keys().map(String::as_str)
+    }
+
+    pub fn active_map(&self) -> &KeyMap {
+        &self.layouts[&self.active]
+    }
+
+    pub fn active_map_mut(&mut self) -> &mut KeyMap {
+        self.layouts.get_mut(&self.active).unwrap()
+    }
+
+    // returns false if there is no layout called name
+    pub fn select(&mut self, name: &str) -> bool {
+        if !self.layouts.contains_key(name) {
+            return false;
+        }
+
+        self.active = name.to_string();
+        true
+    }
+
+    // copies the active layout to name and selects it
+    pub fn save_as(&mut self, name: &str) {
+        let map = self.active_map().clone();
+
+        self.layouts.insert(name.to_string(), map);
+        self.active = name.to_string();
+    }
+
+    // built in layouts go back to their keys, the others are deleted
+    pub fn reset_active(&mut self) {
+        match KeyMap::layout(&self.active) {
+            Some(map) => *self.active_map_mut() = map,
+            None => {
+                self.layouts.remove(&self.active);
+                self.active = KeyMap::LAYOUTS[0].to_string();
+            }
+        }
+    }
+}
+
+// keypad from the keyboard and controller state of the last poll
+pub struct Controls<'a> {
+    pub event_pump: &'a EventPump,
+    pub gamepads: Option<&'a Gamepads>,
+    pub key_map: &'a KeyMap,
+}
+
+impl Input for Controls<'_> {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        self.key_map
+            .update_keys(&self.event_pump.keyboard_state(), self.gamepads, keys);
+    }
+}
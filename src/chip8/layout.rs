@@ -0,0 +1,71 @@
+// placement of the game view inside the window
+
+// area in window pixels, origin at the top left
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+pub struct Layout {
+    pub integer_scale: bool, // only whole multiples of the display size, sharper but smaller
+    pub border: u32,         // minimum space around the display in window pixels
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            integer_scale: true,
+            border: 0,
+        }
+    }
+}
+
+impl Layout {
+    // largest rectangle with square pixels for a width x height display, centered in area
+    // the rest of the area is left as letterbox bars
+    pub fn fit(&self, area: Rect, width: usize, height: usize) -> Rect {
+        let available_width = area.width.saturating_sub(self.border * 2) as f32;
+        let available_height = area.height.saturating_sub(self.border * 2) as f32;
+
+        let scale = (available_width / width as f32).min(available_height / height as f32);
+
+        // integer scaling keeps at least one window pixel per display pixel, even if it doesnt fit
+        let scale = if self.integer_scale {
+            scale.floor().max(1.0)
+        } else {
+            scale
+        };
+
+        let view_width = (width as f32 * scale).round() as u32;
+        let view_height = (height as f32 * scale).round() as u32;
+
+        Rect::new(
+            area.x + (area.width as i32 - view_width as i32) / 2,
+            area.y + (area.height as i32 - view_height as i32) / 2,
+            view_width,
+            view_height,
+        )
+    }
+
+    // smallest area in which fit shows a width x height display at the given scale
+    pub fn area_for(&self, width: usize, height: usize, scale: u32) -> (u32, u32) {
+        (
+            width as u32 * scale + self.border * 2,
+            height as u32 * scale + self.border * 2,
+        )
+    }
+}
@@ -0,0 +1,958 @@
+use super::{
+    fault::{ExecutionFault, FaultPolicies, FaultPolicy},
+    history::{Change, History, Registers},
+    options::{Config, Options},
+    utils::Color,
+};
+
+pub const WIDTH: usize = 64; // Chip8 width
+pub const HEIGHT: usize = 32; // Chip8 height
+
+pub const S_WIDTH: usize = 128; // SuperChip width
+pub const S_HEIGHT: usize = 64; // SuperChip height
+
+pub const MEMORY_SIZE: usize = 0x1000; // 4 KB
+
+const STATE_MAGIC: &[u8] = b"CH8S\x02"; // save state header and version
+
+// size of a save state in bytes
+pub const STATE_SIZE: usize = 5 // header
+    + MEMORY_SIZE
+    + 16 + 8 // registers
+    + 16 * 2 + 1 // stack and stack pointer
+    + 2 + 2 + 1 + 1 // pc, I and timers
+    + 8 + 8 // timer ticks
+    + 8 // random number generator
+    + 3 // state bools and resolution
+    + S_WIDTH * S_HEIGHT;
+
+const SMALL_FONT_SIZE: usize = 5 * 16;
+
+const FONT_DATA: [u8; SMALL_FONT_SIZE + 10 * 10] = [
+    // Chip8 hex font data
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+    // SuperChip font data (no hex chars)
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+// interpreter state without any window, audio or input handling
+// frontends feed it key states and elapsed time and read the screen buffer back
+pub struct Machine {
+    pub v: [u8; 16],             // 16 8-bit registers
+    pub flag_registers: [u8; 8], // special registers, used by FX75* and FX85*
+
+    pub stack: [u16; 16], // stack for storing pc in subroutines
+
+    pub memory: Box<[u8; MEMORY_SIZE]>, // 4 KB memory, 0x0..0x1FF -> chip8 interpreter, 0x200..0xFFF -> rom data
+
+    pub sp: usize, // stack pointer
+    pub pc: u16,   // program counter
+
+    pub r_address: u16,    // address register aka I, FX1E can move it past 0xFFF
+    pub r_delay_timer: u8, // delay timer register
+    pub r_sound_timer: u8, // sound timer register
+
+    delay_tick: f64,
+    sound_tick: f64,
+
+    pub dt_interval: f64,
+    pub st_interval: f64,
+
+    rng: Random, // random number generator for CXNN
+
+    pub keys: [bool; 16],        // currently pressed keys
+    pub polled_keys: [bool; 16], // keys checked by EX9E and EXA1 since the frontend cleared them
+
+    // state bools
+    pub waiting_key_input: bool,
+    pub rom_loaded: bool,
+
+    pub config: Config,
+    pub fault_policies: FaultPolicies,
+
+    pub history: History, // executed instructions for stepping backwards
+
+    pub color_on: Color,  // foreground color
+    pub color_off: Color, // background color
+
+    pub width: usize,  // current buffer width  -> 128 on high res otherwise 64
+    pub height: usize, // current buffer height -> 64  on high res otherwise 32
+
+    // plane bits of every pixel, 0 is off, the colors are applied when the display is drawn
+    pub screen_buffer: Box<[u8; S_WIDTH * S_HEIGHT]>,
+}
+
+impl Machine {
+    pub fn new(options: &Options) -> Self {
+        let mut memory = Box::new([0; MEMORY_SIZE]);
+
+        memory[..FONT_DATA.len()].copy_from_slice(&FONT_DATA);
+
+        Self {
+            v: [0; 16],
+            flag_registers: [0; 8],
+
+            stack: [0; 16],
+
+            memory,
+
+            sp: 0,
+            pc: 0x200,
+
+            r_address: 0,
+            r_delay_timer: 0,
+            r_sound_timer: 0,
+
+            delay_tick: options.dt_interval,
+            sound_tick: options.st_interval,
+
+            dt_interval: options.dt_interval,
+            st_interval: options.st_interval,
+
+            rng: Random::new(options.seed.unwrap_or_else(rand::random)),
+
+            keys: [false; 16],
+            polled_keys: [false; 16],
+
+            waiting_key_input: false,
+            rom_loaded: false,
+
+            config: options.config,
+            fault_policies: options.fault_policies,
+
+            history: History::new(options.history_size),
+
+            color_on: options.color_on,
+            color_off: options.color_off,
+
+            // default to low res
+            width: WIDTH,
+            height: HEIGHT,
+
+            screen_buffer: Box::new([0; S_WIDTH * S_HEIGHT]),
+        }
+    }
+
+    // copy the rom to 0x200 and start executing it, returns the end address of the rom
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<usize, String> {
+        let end = 0x200 + rom.len();
+
+        if end > MEMORY_SIZE {
+            return Err(format!(
+                "invalid rom\nrom size({}) cannot exceed {} bytes",
+                rom.len(),
+                MEMORY_SIZE - 0x200
+            ));
+        }
+
+        self.memory[0x200..end].copy_from_slice(rom);
+        self.memory[end..].fill(0);
+
+        self.reset_state();
+        self.history.clear();
+
+        self.rom_loaded = true;
+
+        self.clear_screen();
+
+        Ok(end)
+    }
+
+    pub fn reset_state(&mut self) {
+        self.v = [0; 16];
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.pc = 0x200;
+        self.r_address = 0;
+        self.r_delay_timer = 0;
+        self.r_sound_timer = 0;
+        self.delay_tick = self.dt_interval;
+        self.sound_tick = self.st_interval;
+
+        self.rom_loaded = false;
+        self.waiting_key_input = false;
+        self.polled_keys = [false; 16];
+
+        self.width = WIDTH;
+        self.height = HEIGHT;
+    }
+
+    // advance the delay and sound timers by the given amount of seconds
+    pub fn update_timers(&mut self, elapsed: f64) {
+        self.delay_tick -= elapsed;
+        self.sound_tick -= elapsed;
+
+        if self.delay_tick <= 0.0 {
+            if self.r_delay_timer > 0 {
+                self.r_delay_timer -= 1;
+            }
+
+            self.delay_tick = self.dt_interval;
+        }
+
+        if self.sound_tick <= 0.0 {
+            if self.r_sound_timer > 0 {
+                self.r_sound_timer -= 1;
+            }
+
+            self.sound_tick = self.st_interval;
+        }
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.r_sound_timer > 0
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.rom_loaded
+    }
+
+    pub fn is_pixel_on(&self, x: usize, y: usize) -> bool {
+        self.screen_buffer[y * S_WIDTH + x] != 0
+    }
+
+    // palette color of a screen buffer value, every lit plane uses the foreground color
+    pub fn color(&self, pixel: u8) -> Color {
+        if pixel == 0 {
+            self.color_off
+        } else {
+            self.color_on
+        }
+    }
+
+    pub fn pixel_color(&self, x: usize, y: usize) -> Color {
+        self.color(self.screen_buffer[y * S_WIDTH + x])
+    }
+
+    pub fn clear_screen(&mut self) {
+        if self.history.is_recording() {
+            for index in 0..self.screen_buffer.len() {
+                self.write_pixel(index, 0);
+            }
+        } else {
+            self.screen_buffer.fill(0);
+        }
+    }
+
+    // undo the last executed instruction, returns false if the history is empty
+    pub fn step_back(&mut self) -> bool {
+        let entry = match self.history.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        for change in entry.changes.iter().rev() {
+            match *change {
+                Change::Memory(address, value) => self.memory[address] = value,
+                Change::Pixel(index, pixel) => self.screen_buffer[index] = pixel,
+            }
+        }
+
+        self.restore_registers(&entry.registers);
+
+        true
+    }
+
+    // snapshot of the execution state for save states, always STATE_SIZE bytes
+    // the history is not included
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_SIZE);
+
+        state.extend_from_slice(STATE_MAGIC);
+        state.extend_from_slice(&*self.memory);
+        state.extend_from_slice(&self.v);
+        state.extend_from_slice(&self.flag_registers);
+
+        for address in self.stack.iter() {
+            state.extend_from_slice(&address.to_le_bytes());
+        }
+
+        state.push(self.sp as u8);
+        state.extend_from_slice(&self.pc.to_le_bytes());
+        state.extend_from_slice(&self.r_address.to_le_bytes());
+        state.push(self.r_delay_timer);
+        state.push(self.r_sound_timer);
+        state.extend_from_slice(&self.delay_tick.to_le_bytes());
+        state.extend_from_slice(&self.sound_tick.to_le_bytes());
+        state.extend_from_slice(&self.rng.state.to_le_bytes());
+
+        state.push(self.waiting_key_input as u8);
+        state.push(self.rom_loaded as u8);
+        state.push((self.width == S_WIDTH) as u8);
+
+        state.extend_from_slice(&*self.screen_buffer);
+
+        state
+    }
+
+    // restore a snapshot taken by save_state, the machine is unchanged on error
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        if state.len() != STATE_SIZE || !state.starts_with(STATE_MAGIC) {
+            return Err("invalid save state".to_string());
+        }
+
+        let mut state = &state[STATE_MAGIC.len()..];
+
+        // stack pointer follows memory, registers and stack
+        let sp = state[MEMORY_SIZE + 16 + 8 + 16 * 2] as usize;
+        if sp > self.stack.len() {
+            return Err("invalid save state\nstack pointer out of range".to_string());
+        }
+
+        let mut take = |len: usize| {
+            let (bytes, rest) = state.split_at(len);
+            state = rest;
+            bytes
+        };
+
+        self.memory.copy_from_slice(take(MEMORY_SIZE));
+        self.v.copy_from_slice(take(16));
+        self.flag_registers.copy_from_slice(take(8));
+
+        for address in self.stack.iter_mut() {
+            *address = u16::from_le_bytes([take(1)[0], take(1)[0]]);
+        }
+
+        take(1); // stack pointer, checked above
+        self.sp = sp;
+        self.pc = u16::from_le_bytes([take(1)[0], take(1)[0]]);
+        self.r_address = u16::from_le_bytes([take(1)[0], take(1)[0]]);
+        self.r_delay_timer = take(1)[0];
+        self.r_sound_timer = take(1)[0];
+
+        let mut tick = [0; 8];
+        tick.copy_from_slice(take(8));
+        self.delay_tick = f64::from_le_bytes(tick);
+        tick.copy_from_slice(take(8));
+        self.sound_tick = f64::from_le_bytes(tick);
+        tick.copy_from_slice(take(8));
+        self.rng.state = u64::from_le_bytes(tick);
+
+        self.waiting_key_input = take(1)[0] != 0;
+        self.rom_loaded = take(1)[0] != 0;
+
+        let high_res = take(1)[0] != 0;
+        self.width = if high_res { S_WIDTH } else { WIDTH };
+        self.height = if high_res { S_HEIGHT } else { HEIGHT };
+
+        self.screen_buffer.copy_from_slice(take(S_WIDTH * S_HEIGHT));
+
+        // the recorded instructions belong to the previous state
+        self.history.clear();
+
+        Ok(())
+    }
+
+    fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            flag_registers: self.flag_registers,
+            stack: self.stack,
+            sp: self.sp,
+            pc: self.pc,
+            r_address: self.r_address,
+            r_delay_timer: self.r_delay_timer,
+            r_sound_timer: self.r_sound_timer,
+            delay_tick: self.delay_tick,
+            sound_tick: self.sound_tick,
+            rng_state: self.rng.state,
+            width: self.width,
+            height: self.height,
+            waiting_key_input: self.waiting_key_input,
+            rom_loaded: self.rom_loaded,
+        }
+    }
+
+    fn restore_registers(&mut self, registers: &Registers) {
+        self.v = registers.v;
+        self.flag_registers = registers.flag_registers;
+        self.stack = registers.stack;
+        self.sp = registers.sp;
+        self.pc = registers.pc;
+        self.r_address = registers.r_address;
+        self.r_delay_timer = registers.r_delay_timer;
+        self.r_sound_timer = registers.r_sound_timer;
+        self.delay_tick = registers.delay_tick;
+        self.sound_tick = registers.sound_tick;
+        self.rng.state = registers.rng_state;
+        self.width = registers.width;
+        self.height = registers.height;
+        self.waiting_key_input = registers.waiting_key_input;
+        self.rom_loaded = registers.rom_loaded;
+    }
+
+    // memory and screen writes go through these so they can be undone
+    fn write_memory(&mut self, address: usize, value: u8) {
+        let address = address % MEMORY_SIZE;
+
+        if self.memory[address] != value {
+            self.history
+                .record(Change::Memory(address, self.memory[address]));
+            self.memory[address] = value;
+        }
+    }
+
+    fn write_pixel(&mut self, index: usize, pixel: u8) {
+        if self.screen_buffer[index] != pixel {
+            self.history
+                .record(Change::Pixel(index, self.screen_buffer[index]));
+            self.screen_buffer[index] = pixel;
+        }
+    }
+
+    // returns the policy to apply for the fault, trapping faults are returned as errors
+    fn raise(&self, fault: ExecutionFault) -> Result<FaultPolicy, ExecutionFault> {
+        match self.fault_policies.get(fault.kind()) {
+            FaultPolicy::Trap => Err(fault),
+            policy => Ok(policy),
+        }
+    }
+
+    // check I..I + len against memory, returns false if the access should be skipped
+    // accesses are always indexed modulo memory size, so wrapping needs no extra work
+    fn check_address_range(&self, len: usize) -> Result<bool, ExecutionFault> {
+        let address = self.r_address as usize;
+
+        if address + len <= MEMORY_SIZE {
+            return Ok(true);
+        }
+
+        let policy = self.raise(ExecutionFault::MemoryOutOfRange { address, len })?;
+
+        Ok(policy == FaultPolicy::Wrap)
+    }
+
+    // there is nothing to wrap for an unknown opcode, so both policies skip it
+    fn unknown_instruction(&self, upper: u8, lower: u8) -> Result<(), ExecutionFault> {
+        self.raise(ExecutionFault::UnknownOpcode(
+            ((upper as u16) << 8) | lower as u16,
+        ))?;
+
+        Ok(())
+    }
+
+    fn push_pc(&mut self) -> Result<bool, ExecutionFault> {
+        if self.sp >= self.stack.len() {
+            match self.raise(ExecutionFault::StackOverflow)? {
+                FaultPolicy::Wrap => self.sp = 0,
+                _ => return Ok(false),
+            }
+        }
+
+        self.stack[self.sp] = self.pc;
+
+        self.sp += 1;
+
+        Ok(true)
+    }
+
+    fn pop_pc(&mut self) -> Result<(), ExecutionFault> {
+        if self.sp == 0 {
+            match self.raise(ExecutionFault::StackUnderflow)? {
+                FaultPolicy::Wrap => self.sp = self.stack.len(),
+                _ => return Ok(()),
+            }
+        }
+
+        self.sp -= 1;
+
+        self.pc = self.stack[self.sp];
+
+        Ok(())
+    }
+
+    // font digits are only defined up to max, wrap with the modulo of max + 1
+    fn font_digit(&self, digit: u8, max: u8) -> Result<Option<u8>, ExecutionFault> {
+        if digit <= max {
+            return Ok(Some(digit));
+        }
+
+        match self.raise(ExecutionFault::InvalidFontDigit(digit))? {
+            FaultPolicy::Wrap => Ok(Some(digit % (max + 1))),
+            _ => Ok(None),
+        }
+    }
+
+    // xor pixel to given locations and return if any pixel is setted off
+    fn set_pixel(&mut self, mut x: usize, mut y: usize, value: u8) -> bool {
+        if x >= self.width {
+            if self.config.draw_behaviour {
+                x %= self.width;
+            }
+            // wrap around
+            else {
+                return false;
+            }
+        }
+
+        if y >= self.height {
+            if self.config.draw_behaviour {
+                y %= self.height;
+            }
+            // wrap around
+            else {
+                return false;
+            }
+        }
+
+        if value == 0 {
+            false
+        } else {
+            let index = y * S_WIDTH + x;
+            let pixel = self.screen_buffer[index];
+
+            // sprites draw to the first plane
+            self.write_pixel(index, pixel ^ 1);
+
+            pixel & 1 != 0
+        }
+    }
+
+    // draw sprite to screen_buffer and if there is a collision set vf to 1 otherwise set vf to 0
+    fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ExecutionFault> {
+        if !self.check_address_range(height * (width / 8))? {
+            return Ok(());
+        }
+
+        let mut collision = false;
+
+        for j in 0..height * width {
+            let i = j / width;
+            let t = j % width;
+
+            let byte =
+                self.memory[(self.r_address as usize + i * (width / 8) + (t / 8)) % MEMORY_SIZE];
+
+            // sprites are stored in big endian format
+            let color = (byte >> (7 - (t % 8))) & 0x1;
+
+            collision |= self.set_pixel(x + t, y + i, color);
+        }
+
+        self.v[0xF] = collision as u8;
+
+        Ok(())
+    }
+
+    // run next instruction
+    // a trapped fault is returned before anything is changed, pc still points to the instruction
+    pub fn step(&mut self) -> Result<(), ExecutionFault> {
+        let pc = self.pc;
+
+        self.history.begin(self.opcode(), self.registers());
+
+        let result = self.execute();
+
+        if result.is_err() {
+            self.pc = pc;
+            self.history.discard();
+        } else {
+            self.history.commit();
+        }
+
+        result
+    }
+
+    // move past the instruction at pc without running it, eg. after a trapped fault
+    // recorded like an executed instruction so stepping back returns to it
+    pub fn skip_instruction(&mut self) {
+        self.history.begin(self.opcode(), self.registers());
+        self.pc = (self.pc + 2) % MEMORY_SIZE as u16;
+        self.history.commit();
+    }
+
+    // instruction at pc
+    fn opcode(&self) -> u16 {
+        let pc = self.pc as usize;
+
+        ((self.memory[pc % MEMORY_SIZE] as u16) << 8) | self.memory[(pc + 1) % MEMORY_SIZE] as u16
+    }
+
+    fn execute(&mut self) -> Result<(), ExecutionFault> {
+        // both bytes of the opcode must be in memory, skipping is not possible so ignore wraps too
+        if self.pc as usize + 1 >= MEMORY_SIZE {
+            self.raise(ExecutionFault::MemoryOutOfRange {
+                address: self.pc as usize,
+                len: 2,
+            })?;
+
+            self.pc %= MEMORY_SIZE as u16;
+        }
+
+        let upper = self.memory[self.pc as usize];
+        let lower = self.memory[(self.pc as usize + 1) % MEMORY_SIZE];
+
+        self.pc += 2;
+
+        let nibbles = [
+            ((upper >> 4) & 0xF) as usize,
+            (upper & 0xF) as usize,
+            ((lower >> 4) & 0xF) as usize,
+            (lower & 0xF) as usize,
+        ];
+
+        let x = nibbles[1];
+        let y = nibbles[2];
+
+        let addr = (((upper & 0xF) as u16) << 8) | lower as u16;
+
+        // opcodes marked with * are new SuperChip instructions
+
+        match nibbles[0] {
+            0x0 => {
+                match lower {
+                    0xE0 => self.clear_screen(), // 00E0 -> CLS
+                    0xEE => self.pop_pc()?,      // 00EE -> RET
+                    0xFE =>
+                    // 00FE* -> LOW
+                    {
+                        // switch to low resolution mode (64x32)
+                        self.clear_screen();
+
+                        self.width = WIDTH;
+                        self.height = HEIGHT;
+                    }
+                    0xFF =>
+                    // 00FF* -> HIGH
+                    {
+                        // switch to high resolution mode (128x64)
+                        self.clear_screen();
+
+                        self.width = S_WIDTH;
+                        self.height = S_HEIGHT;
+                    }
+                    0xFD => self.reset_state(), // 00FD* -> EXIT
+                    0xFB =>
+                    // 00FB* -> SCR
+                    {
+                        // scroll right 4 pixels
+                        for i in 0..self.height {
+                            for t in (0..self.width).rev() {
+                                let pixel = if t < 4 {
+                                    0
+                                } else {
+                                    self.screen_buffer[i * S_WIDTH + t - 4]
+                                };
+                                self.write_pixel(i * S_WIDTH + t, pixel);
+                            }
+                        }
+                    }
+                    0xFC =>
+                    // 00FC* -> SCL
+                    {
+                        // scroll left 4 pixels
+                        for i in 0..self.height {
+                            for t in 0..self.width {
+                                let pixel = if t >= self.width - 4 {
+                                    0
+                                } else {
+                                    self.screen_buffer[i * S_WIDTH + t + 4]
+                                };
+                                self.write_pixel(i * S_WIDTH + t, pixel);
+                            }
+                        }
+                    }
+                    n if n == 0xC0 | nibbles[3] as u8 => {
+                        // scroll down 0 to 15 pixels
+                        for t in 0..self.width {
+                            for i in (0..self.height).rev() {
+                                let pixel = if i < nibbles[3] {
+                                    0
+                                } else {
+                                    self.screen_buffer[(i - nibbles[3]) * S_WIDTH + t]
+                                };
+                                self.write_pixel(i * S_WIDTH + t, pixel);
+                            }
+                        }
+                    }
+                    _ => self.unknown_instruction(upper, lower)?,
+                }
+            }
+            0x1 => self.pc = addr,                    // 1NNN -> JP addr
+            0xB => self.pc = addr + self.v[0] as u16, // BNNN -> JP V0, addr
+
+            0x2 => {
+                if self.push_pc()? {
+                    self.pc = addr;
+                }
+            } // 2NNN -> CALL addr
+
+            0x3 => {
+                if self.v[x] == lower {
+                    self.pc += 2
+                }
+            } // 3XNN -> SE  Vx, byte
+            0x4 => {
+                if self.v[x] != lower {
+                    self.pc += 2
+                }
+            } // 4XNN -> SNE Vx, byte
+            0x5 => {
+                if self.v[x] == self.v[y] {
+                    self.pc += 2
+                }
+            } // 5XY0 -> SE  Vx, Vy
+            0x9 => {
+                if self.v[x] != self.v[y] {
+                    self.pc += 2
+                }
+            } // 9XY0 -> SNE Vx, Vy
+
+            0x6 => self.v[x] = lower, // 6XNN -> LD Vx, byte
+            0x7 => self.v[x] = self.v[x].wrapping_add(lower), // 7XNN -> ADD Vx, byte
+
+            0x8 => {
+                match nibbles[3] {
+                    0x0 => self.v[x] = self.v[y],  // 8XY0 -> LD Vx, Vy
+                    0x1 => self.v[x] |= self.v[y], // 8XY1 -> OR Vx, Vy
+                    0x2 => self.v[x] &= self.v[y], // 8XY2 -> AND Vx, Vy
+                    0x3 => self.v[x] ^= self.v[y], // 8XY3 -> XOR Vx, Vy
+                    0x4 =>
+                    // 8XY4 -> ADD Vx, Vy
+                    {
+                        let result = self.v[x] as u16 + self.v[y] as u16;
+                        self.v[x] = (result % 256) as u8;
+                        self.v[0xF] = (result > 0xFF) as u8;
+                    }
+                    0x5 =>
+                    // 8XY5 -> SUB  Vx, Vy
+                    {
+                        let vf = (self.v[x] >= self.v[y]) as u8;
+                        self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                        self.v[0xF] = vf;
+                    }
+                    0x7 =>
+                    // 8XY7 -> SUBN Vx, Vy
+                    {
+                        let vf = (self.v[y] >= self.v[x]) as u8;
+                        self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                        self.v[0xF] = vf;
+                    }
+                    0x6 =>
+                    // 8XY6 -> SHR Vx {, Vy}
+                    {
+                        if self.config.shift_behaviour {
+                            self.v[0xF] = self.v[y] & 0x1;
+                            self.v[x] = self.v[y] >> 1;
+                        } else {
+                            self.v[0xF] = self.v[x] & 0x1;
+                            self.v[x] >>= 1;
+                        }
+                    }
+                    0xE =>
+                    // 8XYE -> SHL Vx {, Vy}
+                    {
+                        if self.config.shift_behaviour {
+                            self.v[0xF] = (self.v[y] >> 7) & 0x1;
+                            self.v[x] = self.v[y] << 1;
+                        } else {
+                            self.v[0xF] = (self.v[x] >> 7) & 0x1;
+                            self.v[x] <<= 1;
+                        }
+                    }
+                    _ => self.unknown_instruction(upper, lower)?,
+                }
+            }
+            0xA => self.r_address = addr, // ANNN -> LD I, addr
+            0xC => self.v[x] = self.rng.next_byte() & lower, // CXNN -> RND Vx, byte
+            0xD =>
+            // DXYN - DXY0*
+            {
+                match nibbles[3] {
+                    0 => self.draw_sprite(self.v[x] as usize, self.v[y] as usize, 16, 16)?, // DXY0* -> DRW Vx, Vy, 0
+                    height => {
+                        self.draw_sprite(self.v[x] as usize, self.v[y] as usize, 8, height)?
+                    } // DXYN -> DRW Vx, Vy, nibble
+                }
+            }
+            0xE => {
+                match lower {
+                    0x9E => {
+                        let key = self.v[x] as usize % self.keys.len();
+                        self.polled_keys[key] = true;
+
+                        if self.keys[key] {
+                            self.pc += 2
+                        }
+                    } // EX9E -> SKP Vx
+                    0xA1 => {
+                        let key = self.v[x] as usize % self.keys.len();
+                        self.polled_keys[key] = true;
+
+                        if !self.keys[key] {
+                            self.pc += 2
+                        }
+                    } // EXA1 -> SKNP Vx
+                    _ => self.unknown_instruction(upper, lower)?,
+                }
+            }
+            0xF => {
+                match lower {
+                    0x07 => self.v[x] = self.r_delay_timer, // FX07 -> LD Vx, DT
+                    0x15 =>
+                    // FX15 -> LD DT, Vx
+                    {
+                        self.r_delay_timer = self.v[x];
+                        self.delay_tick = self.dt_interval;
+                    }
+                    0x18 =>
+                    // FX18 -> LD ST, Vx
+                    {
+                        self.r_sound_timer = self.v[x];
+                        self.sound_tick = self.st_interval;
+                    }
+                    0x0A =>
+                    // FX0A -> LD Vx, K
+                    {
+                        // repeat this instruction until a key is pressed
+                        match self.keys.iter().position(|pressed| *pressed) {
+                            Some(key) => {
+                                self.v[x] = key as u8;
+                                self.waiting_key_input = false;
+                            }
+                            None => {
+                                self.pc -= 2;
+                                self.waiting_key_input = true;
+                            }
+                        }
+                    }
+                    0x1E => self.r_address = self.r_address.wrapping_add(self.v[x] as u16), // FX1E -> ADD I, Vx
+                    0x29 =>
+                    // FX29 -> LD F, Vx
+                    {
+                        if let Some(digit) = self.font_digit(self.v[x], 0xF)? {
+                            self.r_address = digit as u16 * 5;
+                        }
+                    }
+                    0x30 =>
+                    // FX30* -> LD HF, Vx
+                    {
+                        if let Some(digit) = self.font_digit(self.v[x], 9)? {
+                            self.r_address = SMALL_FONT_SIZE as u16 + digit as u16 * 10;
+                        }
+                    }
+                    0x33 =>
+                    // FX33 -> LD B, Vx
+                    {
+                        if self.check_address_range(3)? {
+                            let digits = [self.v[x] / 100, (self.v[x] / 10) % 10, self.v[x] % 10];
+
+                            for (i, digit) in digits.iter().enumerate() {
+                                self.write_memory(self.r_address as usize + i, *digit);
+                            }
+                        }
+                    }
+                    0x55 =>
+                    // FX55 -> LD [I], Vx
+                    {
+                        // store v0..vx to memory starting at I (address register)
+
+                        let len = x + 1;
+                        let dest = self.r_address as usize;
+
+                        if self.check_address_range(len)? {
+                            let values = self.v;
+
+                            for (i, value) in values[..len].iter().enumerate() {
+                                self.write_memory(dest + i, *value);
+                            }
+
+                            if self.config.store_behaviour {
+                                self.r_address = self.r_address.wrapping_add(len as u16);
+                            }
+                        }
+                    }
+                    0x65 =>
+                    // FX65 -> LD Vx, [I]
+                    {
+                        // read v0..vx from memory starting at I (address register)
+
+                        let len = x + 1;
+                        let src = self.r_address as usize;
+
+                        if self.check_address_range(len)? {
+                            for (i, value) in self.v[..len].iter_mut().enumerate() {
+                                *value = self.memory[(src + i) % MEMORY_SIZE];
+                            }
+
+                            if self.config.store_behaviour {
+                                self.r_address = self.r_address.wrapping_add(len as u16);
+                            }
+                        }
+                    }
+                    0x85 =>
+                    // FX85* -> LD Vx, R
+                    {
+                        let vx = self.v[x].min(7) as usize;
+
+                        // restore the registers v0..vx
+                        self.v[..vx].copy_from_slice(&self.flag_registers[..vx]);
+                    }
+                    0x75 =>
+                    // FX75* -> LD R, Vx
+                    {
+                        let vx = self.v[x].min(7) as usize;
+
+                        // save v0..vx registers to flag registers
+                        self.flag_registers[..vx].copy_from_slice(&self.v[..vx]);
+                    }
+                    _ => self.unknown_instruction(upper, lower)?,
+                }
+            }
+            _ => self.unknown_instruction(upper, lower)?,
+        }
+
+        Ok(())
+    }
+}
+
+// splitmix64, unlike the rand generators its whole state fits in a save state
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+}
@@ -1,135 +1,158 @@
+#[cfg(feature = "gui")]
 use std::{
+    collections::BTreeSet,
     fs, mem,
     path::{Path, PathBuf},
-    time::Instant,
+    time::{Duration, Instant},
     usize,
 };
 
-use rand::{thread_rng, Rng};
-
-use sdl2::{keyboard::Scancode, video::SwapInterval};
+#[cfg(feature = "gui")]
+use sdl2::{
+    keyboard::{Keycode, Scancode},
+    video::SwapInterval,
+};
 
-use imgui::{im_str, ColorEdit, Direction, EditableColor, ImString, Slider};
+#[cfg(feature = "gui")]
+use imgui::{
+    im_str, ColorEdit, Direction, EditableColor, ImStr, ImString, MenuItem, Slider, StyleColor,
+};
 
+pub mod analyzer;
+#[cfg(feature = "gui")]
 mod beeper;
+#[cfg(any(feature = "gui", feature = "tui"))]
+pub mod database;
+pub mod disassembler;
+pub mod fault;
+pub mod filter;
+#[cfg(feature = "gui")]
 mod framebuffer;
+pub mod frontend;
+#[cfg(any(feature = "gui", feature = "tui"))]
+pub mod gamepad;
+pub mod headless;
+pub mod history;
+pub mod image;
+#[cfg(any(feature = "gui", feature = "tui"))]
+pub mod keymap;
+pub mod layout;
+pub mod machine;
+pub mod options;
+pub mod recorder;
+#[cfg(feature = "gui")]
 pub mod renderer;
+#[cfg(any(feature = "gui", feature = "tui"))]
+pub mod settings;
+#[cfg(feature = "gui")]
+pub mod software;
+pub mod symbols;
+#[cfg(feature = "tui")]
+pub mod tui;
 mod utils;
+pub mod view;
+
+#[cfg(feature = "gui")]
+use self::{
+    analyzer::{Analysis, ByteKind},
+    beeper::Beeper,
+    database::Database,
+    disassembler::disassemble_with_symbols,
+    fault::{ExecutionFault, FaultKind, FaultPolicy},
+    filter::{DisplayFilter, Filter},
+    framebuffer::FrameBuffer,
+    frontend::{Audio, Display, Input},
+    keymap::{Bindings, Controls, KeyMap},
+    layout::{Layout, Rect},
+    machine::{Machine, MEMORY_SIZE},
+    options::Options,
+    recorder::Recorder,
+    renderer::Renderer,
+    settings::{rom_hash, RomSettings, Settings},
+    symbols::SymbolTable,
+    view::View,
+};
 
-use self::{beeper::Beeper, framebuffer::FrameBuffer, renderer::Renderer, utils::Color};
-
-const WIDTH: usize = 64; // Chip8 width
-const HEIGHT: usize = 32; // Chip8 height
-
-const S_WIDTH: usize = 128; // SuperChip width
-const S_HEIGHT: usize = 64; // SuperChip height
-
-const MEMORY_SIZE: usize = 0x1000; // 4 KB
-
-const KEY_MAP: [Scancode; 16] = [
-    Scancode::X,
-    Scancode::Num1,
-    Scancode::Num2,
-    Scancode::Num3,
-    Scancode::Q,
-    Scancode::W,
-    Scancode::E,
-    Scancode::A,
-    Scancode::S,
-    Scancode::D,
-    Scancode::Z,
-    Scancode::C,
-    Scancode::Num4,
-    Scancode::R,
-    Scancode::F,
-    Scancode::V,
-];
-
-const SMALL_FONT_SIZE: usize = 5 * 16;
-
-const FONT_DATA: [u8; SMALL_FONT_SIZE + 10 * 10] = [
-    // Chip8 hex font data
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    // SuperChip font data (no hex chars)
-    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
-    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
-    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
-    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
-    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
-    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
-    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
-    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
-    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
-    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+pub use self::utils::Color;
+
+#[cfg(feature = "gui")]
+const RECENT_INSTRUCTIONS: usize = 16; // executed instructions listed by the fault view
+
+// emulated frames run at 60 hz like the timers, independent of the refresh rate of the window
+#[cfg(feature = "gui")]
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+#[cfg(feature = "gui")]
+const MAX_FRAMES: u32 = 4; // frames caught up after a stall, the rest is dropped
+
+#[cfg(feature = "gui")]
+const QUIT_KEY: Keycode = Keycode::Escape;
+#[cfg(feature = "gui")]
+const SCREENSHOT_KEY: Keycode = Keycode::F12;
+#[cfg(feature = "gui")]
+const PLAY_MODE_KEY: Keycode = Keycode::F11;
+
+pub const MENU_BAR_HEIGHT: u32 = 19; // height of the imgui main menu bar with the default font
+
+// keys of the cosmac vip keypad, row by row
+#[cfg(feature = "gui")]
+const KEYPAD_LAYOUT: [usize; 16] = [
+    0x1, 0x2, 0x3, 0xC, //
+    0x4, 0x5, 0x6, 0xD, //
+    0x7, 0x8, 0x9, 0xE, //
+    0xA, 0x0, 0xB, 0xF, //
 ];
 
-struct Config {
-    shift_behaviour: bool, // in 8xy6-8xyE use Vy if this is true otherwise use Vx
-    draw_behaviour: bool,  // wrap screen when drawing sprites if this is true
-    store_behaviour: bool, // in FX55-FX65 increment I after copying if this is true
-}
-
+#[cfg(feature = "gui")]
 pub struct Chip8<'a> {
-    v: [u8; 16],             // 16 8-bit registers
-    flag_registers: [u8; 8], // special registers, used by FX75* and FX85*
+    machine: Machine, // interpreter state
 
-    stack: [u16; 16], // stack for storing pc in subroutines
-
-    memory: Box<[u8; MEMORY_SIZE]>, // 4 KB memory, 0x0..0x1FF -> chip8 interpreter, 0x200..0xFFF -> rom data
-
-    sp: usize, // stack pointer
-    pc: u16,   // program counter
-
-    r_address: u16,    // address register aka I
-    r_delay_timer: u8, // delay timer register
-    r_sound_timer: u8, // sound timer register
-
-    delay_tick: f64,
-    sound_tick: f64,
-
-    dt_interval: f64,
-    st_interval: f64,
     cycles_per_frame: u32,
 
     elapsed_time: Instant, // time elapsed between frames
+    frame_time: Duration,  // time that hasnt been run as emulated frames yet
 
     // state bools
     running: bool,
-    waiting_key_input: bool,
-    rom_loaded: bool,
     vsync_open: bool,
 
-    current_rom_path: PathBuf, // path to currently working rom
+    current_rom_path: PathBuf,  // path to currently working rom
+    rom_hash: Option<String>,   // sha-1 of the currently working rom, keys its settings
+    analysis: Option<Analysis>, // static analysis of the currently working rom
+    symbols: SymbolTable,       // labels of the currently working rom
 
-    config: Config,
+    // debugger
+    breakpoints: BTreeSet<u16>,
+    breakpoint_hit: Option<u16>, // address of the breakpoint that paused execution
+    paused_cycles: u32,          // cycles_per_frame to restore after a breakpoint
+    trace: bool,                 // print every executed instruction to stdout
 
-    // imgui
-    imgui_error_message: String, // error string to create imgui popup windows
-    imgui_lock_to_pc: bool,
+    fault: Option<ExecutionFault>, // trapped fault, nothing runs until it is continued or skipped
 
-    color_on: Color,  // foreground color
-    color_off: Color, // background color
+    screenshot_scale: u32, // size of a chip8 pixel in screenshots, 1 is native resolution
+    last_screenshot: Option<PathBuf>, // path of the last saved screenshot
+    recorder: Option<Recorder>, // records every emulated frame while Some
 
-    width: usize,  // current buffer width  -> 128 on high res otherwise 64
-    height: usize, // current buffer height -> 64  on high res otherwise 32
+    filter: DisplayFilter, // flicker reduction between the screen buffer and the framebuffer
 
-    // buffer to store pixels
-    screen_buffer: Box<[Color; S_WIDTH * S_HEIGHT]>,
+    layout: Layout,          // placement of the game view
+    play_mode: Option<bool>, // Some while the debug windows are hidden, holds the fullscreen state to restore
+    view: View,              // debug windows shown from the View menu
+
+    settings: Option<Settings>, // settings.toml as last read or saved, None if it cant be read
+    rom_settings: RomSettings,  // quirks, speed and palette of every rom that changed them
+    database: Database,         // titles and platforms of known roms
+    applied_settings: Settings, // settings in use, saved again when the settings window changes them
+
+    rom_keys: Option<KeyMap>, // key bindings of the currently working rom
+    bindings: Bindings,       // named key layouts, roms without keys use the active one
+    rebinding: Option<usize>, // chip8 key that the next key or button press is bound to
+    held_key: Option<usize>,  // key of the on screen keypad held down with the mouse
+
+    // imgui
+    imgui_error_message: String, // error string to create imgui popup windows
+    imgui_lock_to_pc: bool,
+    imgui_breakpoint_input: ImString,
+    imgui_layout_name: ImString,
 
     beeper: Beeper, // simple struct for generating square waves
 
@@ -137,147 +160,313 @@ pub struct Chip8<'a> {
     renderer: Option<&'a mut Renderer>,
 }
 
+#[cfg(feature = "gui")]
 impl<'a> Chip8<'a> {
-    pub fn new(renderer: &'a mut Renderer) -> Self {
-        let mut memory = Box::new([0; MEMORY_SIZE]);
+    pub fn new(renderer: &'a mut Renderer, options: Options) -> Self {
+        let mut chip8 = Self {
+            machine: Machine::new(&options),
 
-        memory[..FONT_DATA.len()].copy_from_slice(&FONT_DATA);
+            cycles_per_frame: options.cycles_per_frame,
 
-        // default colors
-        let color_off = Color::new(0x0C, 0x42, 0x71);
-        let color_on = Color::new(0xDF, 0xF9, 0xDC);
+            elapsed_time: Instant::now(),
+            frame_time: Duration::default(),
 
-        let delay_tick_duration = 1.0 / 60.0;
-        let sound_tick_duration = 1.0 / 60.0;
+            running: true,
+            vsync_open: options.vsync,
 
-        Self {
-            v: [0; 16],
-            flag_registers: [0; 8],
+            current_rom_path: PathBuf::new(),
+            rom_hash: None,
+            analysis: None,
+            symbols: SymbolTable::default(),
 
-            stack: [0; 16],
+            breakpoints: BTreeSet::new(),
+            breakpoint_hit: None,
+            paused_cycles: options.cycles_per_frame,
+            trace: false,
 
-            memory,
+            fault: None,
 
-            sp: 0,
-            pc: 0x200,
+            screenshot_scale: 1,
+            last_screenshot: None,
+            recorder: None,
 
-            r_address: 0,
-            r_delay_timer: 0,
-            r_sound_timer: 0,
+            filter: DisplayFilter::default(),
 
-            delay_tick: delay_tick_duration,
-            sound_tick: sound_tick_duration,
+            layout: Layout::default(),
+            play_mode: None,
+            view: View::default(),
 
-            dt_interval: delay_tick_duration,
-            st_interval: sound_tick_duration,
-            cycles_per_frame: 60,
+            settings: None,
+            rom_settings: RomSettings::default(),
+            database: Database::default(),
+            applied_settings: Settings::from_options(&options),
 
-            elapsed_time: Instant::now(),
+            bindings: Bindings::default(),
+            rom_keys: None,
+            rebinding: None,
+            held_key: None,
 
-            running: true,
-            waiting_key_input: false,
-            rom_loaded: false,
-            vsync_open: true,
+            imgui_error_message: String::new(),
+            imgui_lock_to_pc: true,
+            imgui_breakpoint_input: ImString::with_capacity(32),
+            imgui_layout_name: ImString::with_capacity(32),
 
-            current_rom_path: PathBuf::new(),
+            beeper: Beeper::new(&renderer.sdl).unwrap(),
 
-            config: Config {
-                shift_behaviour: true,
-                draw_behaviour: true,
-                store_behaviour: true,
-            },
+            renderer: Some(renderer),
+            framebuffer: FrameBuffer::new(),
+        };
 
-            imgui_error_message: String::new(),
-            imgui_lock_to_pc: true,
+        // a file that cant be read is not saved over, fixing it by hand is easier
+        match Settings::load() {
+            Ok(settings) => chip8.settings = Some(settings),
+            Err(err) => chip8.show_error(format!(
+                "{}\nsettings are not saved until the file is fixed",
+                err
+            )),
+        }
+
+        // the open windows are not a command line option, they only come from the settings file
+        chip8.view = chip8.settings.unwrap_or_default().view;
+        chip8.applied_settings.view = chip8.view;
+
+        match RomSettings::load() {
+            Ok(rom_settings) => chip8.rom_settings = rom_settings,
+            Err(err) => chip8.show_error(err),
+        }
 
-            color_on,
-            color_off,
+        match Database::load() {
+            Ok(database) => chip8.database = database,
+            Err(err) => chip8.show_error(err),
+        }
 
-            // default to low res
-            width: WIDTH,
-            height: HEIGHT,
+        match Bindings::load() {
+            Ok(bindings) => chip8.bindings = bindings,
+            Err(err) => chip8.show_error(err),
+        }
 
-            screen_buffer: Box::new([color_off; S_WIDTH * S_HEIGHT]),
+        // the settings of this rom are already in the options
+        if let Some(rom_path) = options.rom_path {
+            chip8.open_rom(rom_path);
+        }
 
-            beeper: Beeper::new(&renderer.sdl).unwrap(),
+        if let Some(symbols_path) = options.symbols_path {
+            chip8.load_symbols(symbols_path);
+        }
 
-            renderer: Some(renderer),
-            framebuffer: FrameBuffer::new(),
+        if options.start_paused {
+            chip8.cycles_per_frame = 0;
         }
+
+        {
+            let mut callback = chip8.beeper.device.lock();
+            callback.volume = options.volume;
+            callback.freq = options.frequency;
+        }
+
+        if !options.vsync {
+            chip8.set_vsync(false);
+        }
+
+        chip8
     }
 
     pub fn run(mut self) {
         while self.running {
             self.poll_events();
 
-            for _ in 0..self.cycles_per_frame {
-                if self.is_pc_valid() {
-                    self.run_next_opcode();
-                } else {
-                    // draw random colors for fun
-                    for i in 0..self.width * self.height {
-                        self.screen_buffer[(i / self.width) * S_WIDTH + (i % self.width)] =
-                            Color::rand();
-                    }
-                    break;
-                }
-            }
+            self.run_frames();
 
             self.draw();
         }
+
+        self.stop_recording();
     }
 
-    fn wait_key_input(&mut self) -> usize {
-        let mut result = None;
-        self.waiting_key_input = true;
+    fn poll_events(&mut self) {
+        if self.renderer.as_mut().unwrap().poll_events() {
+            self.running = false;
+            return;
+        }
 
-        while self.running && self.waiting_key_input && !result.is_some() {
-            self.poll_events();
+        // escape leaves play mode first and quits otherwise
+        if self
+            .renderer
+            .as_ref()
+            .unwrap()
+            .key_presses
+            .contains(&QUIT_KEY)
+        {
+            if self.play_mode.is_some() {
+                self.set_play_mode(false);
+            } else {
+                self.running = false;
+                return;
+            }
+        }
 
-            for (index, key) in KEY_MAP.iter().enumerate() {
-                if self.is_key_pressed(*key) {
-                    result = Some(index);
-                    break;
-                }
+        // the first key or button pressed after clicking a binding goes to it instead of the hotkeys
+        if let Some(key) = self.rebinding {
+            let renderer = self.renderer.as_ref().unwrap();
+
+            let scancode = renderer
+                .key_presses
+                .iter()
+                .find_map(|keycode| Scancode::from_keycode(*keycode));
+            let pad_input = renderer.pad_presses.first().copied();
+
+            match (scancode, pad_input) {
+                (Some(scancode), _) => self.key_map_mut().bind(key, scancode),
+                (None, Some(input)) => self.key_map_mut().bind_pad(key, input),
+                (None, None) => {}
             }
 
-            self.draw();
+            if scancode.is_some() || pad_input.is_some() {
+                self.renderer.as_mut().unwrap().key_presses.clear();
+
+                self.rebinding = None;
+                self.save_key_bindings();
+            }
+        }
+
+        if self
+            .renderer
+            .as_ref()
+            .unwrap()
+            .key_presses
+            .contains(&SCREENSHOT_KEY)
+        {
+            let path = self.output_path("png");
+            self.save_screenshot(path);
+        }
+
+        if self
+            .renderer
+            .as_ref()
+            .unwrap()
+            .key_presses
+            .contains(&PLAY_MODE_KEY)
+        {
+            self.set_play_mode(self.play_mode.is_none());
         }
-        self.waiting_key_input = false;
 
-        result.unwrap_or(0)
+        // faults and errors are shown in the debug windows
+        if self.play_mode.is_some()
+            && (self.fault.is_some() || !self.imgui_error_message.is_empty())
+        {
+            self.set_play_mode(false);
+        }
     }
 
-    fn poll_events(&mut self) {
-        if self.renderer.as_mut().unwrap().poll_events() {
-            self.running = false;
+    // run the emulated frames that are due since the last call
+    // nothing runs while paused or a fault is shown, the timers stop and only the keys are updated
+    fn run_frames(&mut self) {
+        let elapsed = self.elapsed_time.elapsed();
+        self.elapsed_time = Instant::now();
+
+        if self.cycles_per_frame == 0 || self.fault.is_some() || !self.machine.is_running() {
+            self.frame_time = Duration::default();
+            self.beeper.set_beeping(false);
+
+            let mut keys = self.machine.keys;
+            self.controls().update_keys(&mut keys);
+            self.machine.keys = keys;
+
             return;
         }
 
-        let elapsed = self.elapsed_time.elapsed().as_secs_f64();
-        self.elapsed_time = Instant::now();
+        self.frame_time = (self.frame_time + elapsed).min(FRAME_TIME * MAX_FRAMES);
+
+        while self.frame_time >= FRAME_TIME && self.cycles_per_frame > 0 && self.fault.is_none() {
+            self.frame_time -= FRAME_TIME;
+            self.run_frame();
+        }
+    }
+
+    // one frame of instructions, stops early at a breakpoint or a trapped fault
+    fn run_frame(&mut self) {
+        let renderer = self.renderer.as_ref().unwrap();
+
+        let mut input = ChipInput {
+            controls: Controls {
+                event_pump: &renderer.event_pump,
+                gamepads: renderer.gamepads.as_ref(),
+                key_map: match self.rom_keys.as_ref() {
+                    Some(key_map) => key_map,
+                    None => self.bindings.active_map(),
+                },
+            },
+            held_key: self.held_key,
+        };
+
+        // the filter and the recorder advance once per emulated frame, draw only shows the result
+        let filter = &mut self.filter;
+        let recorder = &mut self.recorder;
+        let mut record_error = None;
 
-        self.delay_tick -= elapsed;
-        self.sound_tick -= elapsed;
+        let mut display = |machine: &Machine| {
+            filter.apply(machine);
 
-        if self.delay_tick <= 0.0 {
-            if self.r_delay_timer > 0 {
-                self.r_delay_timer -= 1;
+            if let Some(Err(err)) = recorder.as_mut().map(|recorder| recorder.capture(machine)) {
+                *recorder = None;
+                record_error = Some(err);
             }
+        };
 
-            self.delay_tick = self.dt_interval;
-        }
+        let breakpoints = &self.breakpoints;
+        let breakpoint_hit = &mut self.breakpoint_hit;
+        let symbols = &self.symbols;
+        let trace = self.trace;
+
+        let before_step = |machine: &Machine| {
+            if breakpoints.contains(&machine.pc) && *breakpoint_hit != Some(machine.pc) {
+                // pause, execution continues from the breakpoint once resumed
+                *breakpoint_hit = Some(machine.pc);
+                return false;
+            }
 
-        if self.sound_tick <= 0.0 {
-            if self.r_sound_timer > 0 {
-                self.r_sound_timer -= 1;
+            *breakpoint_hit = None;
 
-                if self.r_sound_timer == 0 {
-                    self.beeper.device.pause();
-                }
+            if trace {
+                print_trace(machine, symbols);
             }
 
-            self.sound_tick = self.st_interval;
+            true
+        };
+
+        let result = frontend::run_frame_with(
+            &mut self.machine,
+            self.cycles_per_frame,
+            FRAME_TIME.as_secs_f64(),
+            &mut input,
+            &mut self.beeper,
+            &mut display,
+            before_step,
+        );
+
+        if let Some(err) = record_error {
+            self.show_error(err);
+        }
+
+        if let Err(fault) = result {
+            self.fault = Some(fault);
+        } else if self.breakpoint_hit.is_some() {
+            self.paused_cycles = self.cycles_per_frame;
+            self.cycles_per_frame = 0;
+        }
+    }
+
+    // keyboard and controllers with the bindings of the current rom
+    fn controls(&self) -> ChipInput<'_> {
+        let renderer = self.renderer.as_ref().unwrap();
+
+        ChipInput {
+            controls: Controls {
+                event_pump: &renderer.event_pump,
+                gamepads: renderer.gamepads.as_ref(),
+                key_map: self.key_map(),
+            },
+            held_key: self.held_key,
         }
     }
 
@@ -289,51 +478,166 @@ impl<'a> Chip8<'a> {
         let width = renderer.window_width;
         let height = renderer.window_height;
 
-        let src = (0, 0, self.width as _, self.height as _);
-        let dest = (0, height as i32, (width / 2) as i32, (height / 2) as i32);
+        let play_mode = self.play_mode.is_some();
+
+        // the game view takes the whole window in play mode, the space below the menu bar when
+        // every debug window is hidden and the top left quarter otherwise
+        let top = MENU_BAR_HEIGHT as i32;
+
+        let area = if play_mode {
+            Rect::new(0, 0, width, height)
+        } else if !self.view.any_open() {
+            Rect::new(0, top, width, height.saturating_sub(MENU_BAR_HEIGHT))
+        } else {
+            Rect::new(
+                0,
+                top,
+                width / 2,
+                (height / 2).saturating_sub(MENU_BAR_HEIGHT),
+            )
+        };
 
-        self.framebuffer.update_buffer(
-            S_WIDTH as _,
-            S_HEIGHT as _,
-            self.screen_buffer.as_ptr() as _,
-            gl::RGBA8,
-            gl::RGBA,
+        let view = self
+            .layout
+            .fit(area, self.machine.width, self.machine.height);
+
+        // opengl counts from the bottom left, flip the view so the first row ends up on top
+        let src = (0, 0, self.machine.width as _, self.machine.height as _);
+        let dest = (
+            view.x,
+            height as i32 - view.y,
+            view.x + view.width as i32,
+            height as i32 - view.y - view.height as i32,
         );
+
+        if !self.machine.is_running() {
+            // draw random colors for fun
+            self.framebuffer
+                .present_noise(self.machine.width, self.machine.height);
+        } else if self.filter.filter == Filter::Off || self.cycles_per_frame == 0 {
+            // instructions stepped while paused show up at once
+            self.framebuffer.present(&self.machine);
+        } else {
+            self.framebuffer
+                .present_filtered(&self.machine, self.filter.brightness());
+        }
+
         self.framebuffer.draw_buffer(src, dest);
 
         let mut run_next_opcode = false;
+        let mut run_previous_opcode = false;
+        let mut toggle_play_mode = false;
         let mut vsync_open = self.vsync_open;
+        let mut windows = self.view;
+        let mut editing = false;
+
+        // set again by the keypad while its key is held
+        self.held_key = None;
+
+        let controllers: Vec<String> = match renderer.gamepads.as_ref() {
+            Some(gamepads) => gamepads.names().collect(),
+            None => Vec::new(),
+        };
 
         // ugly imgui rendering
         renderer.render(|ui| {
-            let imgui_window = |name, pos, width| {
-                imgui::Window::new(name)
-                    .resizable(false)
-                    .collapsible(false)
-                    .movable(false)
-                    .position(pos, imgui::Condition::Always)
-                    .size(
-                        [width as f32, (height / 2) as f32],
-                        imgui::Condition::Always,
-                    )
+            if play_mode {
+                return;
+            }
+
+            editing = ui.is_any_item_active();
+
+            let mut reset_layout = false;
+
+            ui.main_menu_bar(|| {
+                ui.menu(im_str!("View"), true, || {
+                    for (name, open) in windows.windows().iter_mut() {
+                        MenuItem::new(&ImString::new(*name)).build_with_ref(ui, open);
+                    }
+
+                    ui.separator();
+
+                    if MenuItem::new(im_str!("Play Mode"))
+                        .shortcut(&im_str!("{:?}", PLAY_MODE_KEY))
+                        .build(ui)
+                    {
+                        toggle_play_mode = true;
+                    }
+
+                    if MenuItem::new(im_str!("Reset Layout")).build(ui) {
+                        reset_layout = true;
+                    }
+                });
+            });
+
+            // windows start tiled below the game view, after that imgui remembers where they were left
+            let condition = if reset_layout {
+                windows = View::default();
+                imgui::Condition::Always
+            } else {
+                imgui::Condition::FirstUseEver
             };
 
-            imgui_window(im_str!("Settings"), [(width / 2) as f32, 0.0], width / 2).build(
-                ui,
-                || {
-                    if self.rom_loaded {
-                        let file_name =
-                            self.current_rom_path.file_name().unwrap().to_str().unwrap();
-                        ui.text(format!("Currently Running -> {}", file_name));
-                    } else {
-                        ui.text("Waiting Chip8/SuperChip Rom");
+            let top = MENU_BAR_HEIGHT as f32;
+
+            if windows.settings {
+                debug_window(
+                    im_str!("Settings"),
+                    [(width / 2) as f32, top],
+                    [(width / 2) as f32, (height / 2) as f32 - top],
+                    condition,
+                    &mut windows.settings,
+                )
+                .build(ui, || {
+                    let info = self
+                        .rom_hash
+                        .as_ref()
+                        .and_then(|hash| self.database.get(hash));
+
+                    match info {
+                        Some(info) if self.machine.rom_loaded => {
+                            ui.text(format!("Currently Running -> {}", info.title));
+
+                            let mut credits = info.authors.join(", ");
+                            if let Some(release) = info.release.as_ref() {
+                                credits = format!("{} ({})", credits, release);
+                            }
+                            if let Some(platform) = info.platform.as_ref() {
+                                credits = format!("{} for {}", credits, platform);
+                            }
+                            ui.text_disabled(credits);
+
+                            if let Some(description) = info.description.as_ref() {
+                                ui.text_wrapped(&ImString::new(description.as_str()));
+                            }
+
+                            if !info.keys.is_empty() {
+                                let keys: Vec<_> = info
+                                    .keys
+                                    .iter()
+                                    .map(|(name, key)| format!("{} {:X}", name, key))
+                                    .collect();
+                                ui.text_wrapped(&im_str!("Keys: {}", keys.join(", ")));
+                            }
+                        }
+                        None if self.machine.rom_loaded => {
+                            let file_name =
+                                self.current_rom_path.file_name().unwrap().to_str().unwrap();
+                            ui.text(format!("Currently Running -> {}", file_name));
+                        }
+                        _ => ui.text("Waiting Chip8/SuperChip Rom"),
                     }
 
                     if ui.small_button(im_str!("open rom")) {
                         if let Some(rom_path) =
                             tinyfiledialogs::open_file_dialog("Open", "./", None)
                         {
+                            // labels and breakpoints belong to the previous rom
+                            self.symbols = SymbolTable::default();
+                            self.breakpoints.clear();
+
                             self.open_rom(rom_path);
+                            self.apply_rom_settings();
                         }
                     }
 
@@ -354,6 +658,82 @@ impl<'a> Chip8<'a> {
                     ui.same_line(0.0);
                     ui.checkbox(im_str!("vsync"), &mut vsync_open);
 
+                    if ui.small_button(im_str!("import rom database")) {
+                        if let Some(programs_path) = tinyfiledialogs::open_file_dialog(
+                            "Import chip-8-database programs.json",
+                            "./programs.json",
+                            Some((&["*.json"], "JSON")),
+                        ) {
+                            match Database::import(programs_path) {
+                                Ok(database) => {
+                                    self.database = database;
+                                    self.apply_rom_settings();
+                                }
+                                Err(err) => self.show_error(err),
+                            }
+                        }
+                    }
+                    ui.same_line(0.0);
+                    ui.text(format!("{} known roms", self.database.len()));
+
+                    if self.machine.rom_loaded && ui.small_button(im_str!("export cfg")) {
+                        if let Some(dot_path) = tinyfiledialogs::save_file_dialog(
+                            "Export Control Flow Graph",
+                            "./cfg.dot",
+                        ) {
+                            self.export_cfg(dot_path);
+                        }
+                    }
+
+                    // screenshots
+                    ui.separator();
+                    if ui.small_button(im_str!("screenshot")) {
+                        let default_path = self.output_path("png");
+
+                        if let Some(png_path) = tinyfiledialogs::save_file_dialog(
+                            "Save Screenshot",
+                            default_path.to_str().unwrap_or("./screenshot.png"),
+                        ) {
+                            self.save_screenshot(png_path);
+                        }
+                    }
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("native"), &mut self.screenshot_scale, 1);
+                    ui.same_line(0.0);
+                    Slider::new(im_str!("Screenshot Scale"))
+                        .range(1..=16)
+                        .build(ui, &mut self.screenshot_scale);
+
+                    match self.last_screenshot.as_ref() {
+                        Some(path) => ui.text(format!("Saved {:?}", path)),
+                        None => ui.text(format!("{:?} saves a screenshot", SCREENSHOT_KEY)),
+                    }
+
+                    // recording
+                    match self.recorder.as_ref() {
+                        Some(recorder) => {
+                            let seconds = recorder.frames() as f32 / 60.0;
+
+                            if ui.small_button(im_str!("stop recording")) {
+                                self.stop_recording();
+                            }
+                            ui.same_line(0.0);
+                            ui.text_colored([1.0, 0.0, 0.0, 1.0], format!("{:.1}s", seconds));
+                        }
+                        None => {
+                            if ui.small_button(im_str!("record")) {
+                                let default_path = self.output_path("gif");
+
+                                if let Some(record_path) = tinyfiledialogs::save_file_dialog(
+                                    "Record (.gif or .y4m)",
+                                    default_path.to_str().unwrap_or("./recording.gif"),
+                                ) {
+                                    self.start_recording(record_path);
+                                }
+                            }
+                        }
+                    }
+
                     if ui.button(im_str!("*##0"), [0.0, 0.0]) {
                         self.cycles_per_frame = 60
                     }
@@ -363,20 +743,20 @@ impl<'a> Chip8<'a> {
                         .build(ui, &mut self.cycles_per_frame);
 
                     if ui.button(im_str!("*##1"), [0.0, 0.0]) {
-                        self.dt_interval = 1.0 / 60.0
+                        self.machine.dt_interval = 1.0 / 60.0
                     }
                     ui.same_line(0.0);
                     Slider::new(im_str!("Delay Tick Interval"))
                         .range(0.0..=1.0)
-                        .build(ui, &mut self.dt_interval);
+                        .build(ui, &mut self.machine.dt_interval);
 
                     if ui.button(im_str!("*##2"), [0.0, 0.0]) {
-                        self.st_interval = 1.0 / 60.0
+                        self.machine.st_interval = 1.0 / 60.0
                     }
                     ui.same_line(0.0);
                     Slider::new(im_str!("Sound Tick Interval"))
                         .range(0.0..=1.0)
-                        .build(ui, &mut self.st_interval);
+                        .build(ui, &mut self.machine.st_interval);
 
                     // pause and step over
                     ui.separator();
@@ -384,55 +764,96 @@ impl<'a> Chip8<'a> {
 
                     if self.cycles_per_frame == 0 {
                         ui.same_line(0.0);
-                        run_next_opcode =
-                            ui.arrow_button(im_str!("1"), Direction::Right) && self.is_pc_valid();
+                        run_previous_opcode = ui.arrow_button(im_str!("0"), Direction::Left);
+                        ui.same_line(0.0);
+                        run_next_opcode = ui.arrow_button(im_str!("1"), Direction::Right)
+                            && self.machine.is_running();
                         ui.same_line(0.0);
-                        ui.text("Step Over");
+                        ui.text(format!(
+                            "Step Back ({}) / Step Over",
+                            self.machine.history.len()
+                        ));
                     }
 
                     // config checkboxes
                     ui.separator();
                     ui.checkbox(
                         im_str!("Shift Vy in 8XYE and 8XY6"),
-                        &mut self.config.shift_behaviour,
+                        &mut self.machine.config.shift_behaviour,
                     );
                     ui.checkbox(
                         im_str!("Wrap around screen when drawing sprites"),
-                        &mut self.config.draw_behaviour,
+                        &mut self.machine.config.draw_behaviour,
                     );
                     ui.checkbox(
                         im_str!("Increment I after FX55 and FX65"),
-                        &mut self.config.store_behaviour,
+                        &mut self.machine.config.store_behaviour,
                     );
 
+                    // fault policies
+                    ui.separator();
+                    for kind in FaultKind::ALL.iter() {
+                        let policy = self.machine.fault_policies.get_mut(*kind);
+
+                        for value in FaultPolicy::ALL.iter() {
+                            ui.radio_button(
+                                &im_str!("{}##{}", value.name(), kind.name()),
+                                policy,
+                                *value,
+                            );
+                            ui.same_line(0.0);
+                        }
+
+                        ui.text(kind.name());
+                    }
+
                     // color sliders
-                    let handle_color = |name, screen_buffer: &mut [Color], color: &mut Color| {
+                    // the palette is applied when the display is drawn
+                    let handle_color = |name, color: &mut Color| {
                         let mut new_color = color.as_array();
                         ColorEdit::new(name, EditableColor::Float3(&mut new_color)).build(ui);
-                        let new_color = Color::from_array(new_color);
+                        *color = Color::from_array(new_color);
+                    };
 
-                        if *color != new_color {
-                            screen_buffer.iter_mut().for_each(|c| {
-                                if c == color {
-                                    *c = new_color;
-                                }
-                            });
+                    ui.separator();
+                    handle_color(im_str!("Background Color"), &mut self.machine.color_off);
+                    handle_color(im_str!("Foreground Color"), &mut self.machine.color_on);
 
-                            *color = new_color;
+                    // flicker reduction
+                    ui.text("Display Filter:");
+                    for filter in Filter::ALL.iter() {
+                        ui.same_line(0.0);
+                        ui.radio_button(
+                            &im_str!("{}##filter", filter.name()),
+                            &mut self.filter.filter,
+                            *filter,
+                        );
+                    }
+
+                    match self.filter.filter {
+                        Filter::Phosphor => {
+                            Slider::new(im_str!("Half-life (frames)"))
+                                .range(0.1..=30.0)
+                                .build(ui, &mut self.filter.half_life);
                         }
-                    };
+                        Filter::Deflicker => {
+                            Slider::new(im_str!("Clear Delay (frames)"))
+                                .range(0..=10)
+                                .build(ui, &mut self.filter.clear_delay);
+                        }
+                        Filter::Off | Filter::Blend => {}
+                    }
 
-                    ui.separator();
-                    handle_color(
-                        im_str!("Background Color"),
-                        &mut *self.screen_buffer,
-                        &mut self.color_off,
-                    );
-                    handle_color(
-                        im_str!("Foreground Color"),
-                        &mut *self.screen_buffer,
-                        &mut self.color_on,
-                    );
+                    // game view layout
+                    ui.checkbox(im_str!("integer scaling"), &mut self.layout.integer_scale);
+                    ui.same_line(0.0);
+                    Slider::new(im_str!("Border"))
+                        .range(0..=64)
+                        .build(ui, &mut self.layout.border);
+
+                    if ui.small_button(&im_str!("play ({:?})", PLAY_MODE_KEY)) {
+                        toggle_play_mode = true;
+                    }
 
                     // audio
                     ui.separator();
@@ -457,12 +878,18 @@ impl<'a> Chip8<'a> {
                             .range(0.0..=2000.0)
                             .build(ui, &mut callback.freq);
                     }
-                },
-            );
+                });
+            }
 
-            imgui_window(im_str!("Registers"), [0.0, (height / 2) as f32], width / 3).build(
-                ui,
-                || {
+            if windows.registers {
+                debug_window(
+                    im_str!("Registers"),
+                    [0.0, (height / 2) as f32],
+                    [(width / 4) as f32, (height / 2) as f32],
+                    condition,
+                    &mut windows.registers,
+                )
+                .build(ui, || {
                     let print_registers = |registers: &[u8]| {
                         for (index, reg) in registers.iter().enumerate() {
                             ui.text(format!("V{:x}: {:#04x}", index, reg));
@@ -474,579 +901,880 @@ impl<'a> Chip8<'a> {
 
                     ui.set_window_font_scale(1.3);
 
-                    print_registers(&self.v);
+                    print_registers(&self.machine.v);
 
-                    ui.text(format!("I : {:#x}", self.r_address));
-                    ui.text(format!("DT: {:#x}", self.r_delay_timer));
-                    ui.text(format!("ST: {:#x}", self.r_sound_timer));
-                    ui.text(format!("PC: {:#x}", self.pc));
-                    ui.text(format!("SP: {:#x}", self.sp));
+                    ui.text(format!("I : {:#x}", self.machine.r_address));
+                    ui.text(format!("DT: {:#x}", self.machine.r_delay_timer));
+                    ui.text(format!("ST: {:#x}", self.machine.r_sound_timer));
+                    ui.text(format!("PC: {:#x}", self.machine.pc));
+                    ui.text(format!("SP: {:#x}", self.machine.sp));
 
                     ui.separator();
                     ui.text("Flag Registers:");
 
-                    print_registers(&self.flag_registers);
+                    print_registers(&self.machine.flag_registers);
 
                     ui.set_window_font_scale(1.0);
-                },
-            );
-
-            imgui_window(
-                im_str!("Memory"),
-                [(width / 3) as f32, (height / 2) as f32],
-                width / 3,
-            )
-            .menu_bar(true)
-            .build(ui, || {
-                ui.menu_bar(|| {
-                    ui.checkbox(im_str!("Lock to PC"), &mut self.imgui_lock_to_pc);
                 });
+            }
 
-                let mut iter = self.memory.iter().enumerate();
-
-                if self.pc % 2 == 1 {
-                    iter.next();
-                }
-
-                while let Some((index, first)) = iter.next() {
-                    let second = if let Some(second) = iter.next() {
-                        second
-                    } else {
-                        break;
-                    };
+            if windows.debugger {
+                debug_window(
+                    im_str!("Debugger"),
+                    [(width / 4) as f32, (height / 2) as f32],
+                    [(width / 4) as f32, (height / 2) as f32],
+                    condition,
+                    &mut windows.debugger,
+                )
+                .build(ui, || {
+                    if ui.small_button(im_str!("load symbols")) {
+                        if let Some(symbols_path) =
+                            tinyfiledialogs::open_file_dialog("Open Symbols", "./", None)
+                        {
+                            self.load_symbols(symbols_path);
+                        }
+                    }
+                    ui.same_line(0.0);
+                    ui.checkbox(im_str!("Trace"), &mut self.trace);
 
-                    let string = format!("{:#x}: {:02X} {:02X}", index, first, second.1);
+                    if let Some(address) = self.breakpoint_hit {
+                        ui.text_colored(
+                            [1.0, 0.0, 0.0, 1.0],
+                            format!("Breakpoint -> {}", self.symbols.describe(address)),
+                        );
 
-                    if index as u16 == self.pc {
-                        ui.text_colored([1.0, 0.0, 0.0, 1.0], string);
-                        if self.imgui_lock_to_pc {
-                            ui.set_scroll_here_y();
+                        if self.cycles_per_frame == 0 && ui.small_button(im_str!("continue")) {
+                            self.cycles_per_frame = self.paused_cycles;
                         }
-                    } else {
-                        ui.text(string);
                     }
-                }
-            });
 
-            imgui_window(
-                im_str!("Keyboard"),
-                [(width * 2 / 3) as f32, (height / 2) as f32],
-                width / 3,
-            )
-            .build(ui, || {
-                if self.waiting_key_input {
-                    ui.text("Waiting key input");
-                }
+                    ui.separator();
+                    ui.text("Call Stack:");
 
-                for (index, value) in KEY_MAP.iter().enumerate() {
-                    ui.label_text(&im_str!("{:?}", value), &im_str!("{:X} -> ", index));
-                }
-            });
+                    for (index, address) in self.machine.stack[..self.machine.sp]
+                        .iter()
+                        .enumerate()
+                        .rev()
+                    {
+                        ui.text(format!("{:x}: {}", index, self.symbols.describe(*address)));
+                    }
 
-            let mut popup_id = Default::default();
+                    ui.separator();
+                    ui.text("Breakpoints:");
 
-            if !self.imgui_error_message.is_empty() {
-                // BAD!!
-                // give every different sized error message a unique id
-                // otherwise imgui doesnt render it properly
-                popup_id = unsafe {
-                    ImString::from_utf8_unchecked(
-                        self.imgui_error_message.len().to_ne_bytes().to_vec(),
-                    )
-                };
-                ui.open_popup(&popup_id);
-            }
+                    ui.input_text(im_str!("##breakpoint"), &mut self.imgui_breakpoint_input)
+                        .build();
+                    ui.same_line(0.0);
 
-            ui.popup_modal(&popup_id)
-                .scroll_bar(false)
-                .movable(false)
-                .resizable(false)
-                .title_bar(false)
-                .build(|| {
-                    ui.text(&self.imgui_error_message);
+                    // breakpoints can be given as labels or hex addresses
+                    if ui.small_button(im_str!("add")) {
+                        if let Some(address) =
+                            self.symbols.resolve(self.imgui_breakpoint_input.to_str())
+                        {
+                            self.breakpoints.insert(address);
+                            self.imgui_breakpoint_input.clear();
+                        }
+                    }
 
-                    if ui.button(im_str!("OK"), [0.0, 0.0]) {
-                        self.imgui_error_message = String::new();
+                    let mut removed = None;
 
-                        self.reset_state();
+                    for address in self.breakpoints.iter() {
+                        if ui.small_button(&im_str!("x##{}", address)) {
+                            removed = Some(*address);
+                        }
+                        ui.same_line(0.0);
+                        ui.text(self.symbols.describe(*address));
+                    }
+
+                    if let Some(address) = removed {
+                        self.breakpoints.remove(&address);
+                    }
+                });
+            }
+
+            if windows.memory {
+                debug_window(
+                    im_str!("Memory"),
+                    [(width / 2) as f32, (height / 2) as f32],
+                    [(width / 4) as f32, (height / 2) as f32],
+                    condition,
+                    &mut windows.memory,
+                )
+                .menu_bar(true)
+                .build(ui, || {
+                    ui.menu_bar(|| {
+                        ui.checkbox(im_str!("Lock to PC"), &mut self.imgui_lock_to_pc);
+                    });
+
+                    let mut index = self.machine.pc as usize % 2;
+
+                    while index + 1 < MEMORY_SIZE {
+                        let first = self.machine.memory[index];
+                        let second = self.machine.memory[index + 1];
+
+                        let kind = self
+                            .analysis
+                            .as_ref()
+                            .map_or(ByteKind::Unknown, |analysis| analysis.byte_kind(index));
+
+                        // only decode instructions that the analyzer found, show sprite data as bits
+                        let decoded = match kind {
+                            ByteKind::Code => {
+                                disassemble_with_symbols(first, second, &self.symbols)
+                                    .unwrap_or_default()
+                            }
+                            ByteKind::Data => format!("{:08b}{:08b}", first, second)
+                                .replace('0', ".")
+                                .replace('1', "#"),
+                            ByteKind::Unknown => String::new(),
+                        };
+
+                        if let Some(name) = self.symbols.name(index as u16) {
+                            ui.text_colored([1.0, 1.0, 0.0, 1.0], format!("{}:", name));
+                        }
+
+                        let string =
+                            format!("{:#x}: {:02X} {:02X}  {}", index, first, second, decoded);
+
+                        if index as u16 == self.machine.pc {
+                            ui.text_colored([1.0, 0.0, 0.0, 1.0], string);
+                            if self.imgui_lock_to_pc {
+                                ui.set_scroll_here_y();
+                            }
+                        } else {
+                            ui.text(string);
+                        }
+
+                        index += 2;
+                    }
+                });
+            }
+
+            if windows.keyboard {
+                debug_window(
+                    im_str!("Keyboard"),
+                    [(width * 3 / 4) as f32, (height / 2) as f32],
+                    [(width / 4) as f32, (height / 2) as f32],
+                    condition,
+                    &mut windows.keyboard,
+                )
+                .build(ui, || {
+                    // big enough for a finger, four keys wide
+                    let size = (ui.content_region_avail()[0] / 4.0 - 8.0).clamp(24.0, 64.0);
+
+                    for (index, key) in KEYPAD_LAYOUT.iter().copied().enumerate() {
+                        if index % 4 != 0 {
+                            ui.same_line(0.0);
+                        }
+
+                        // pressed keys are green, keys the rom is checking are yellow
+                        let polled =
+                            self.machine.polled_keys[key] || self.machine.waiting_key_input;
+                        let color = if self.machine.keys[key] {
+                            Some([0.0, 0.6, 0.0, 1.0])
+                        } else if polled {
+                            Some([0.6, 0.5, 0.0, 1.0])
+                        } else {
+                            None
+                        };
+
+                        let token =
+                            color.map(|color| ui.push_style_color(StyleColor::Button, color));
+                        ui.button(&im_str!("{:X}##keypad", key), [size, size]);
+                        if let Some(token) = token {
+                            token.pop(ui);
+                        }
+
+                        // held as long as the mouse button or the finger stays down
+                        if ui.is_item_active() {
+                            self.held_key = Some(key);
+                        }
+                    }
+
+                    if self.machine.waiting_key_input {
+                        ui.text("Waiting for any key (FX0A)");
+                    } else {
+                        let polled: Vec<String> = (0..16)
+                            .filter(|key| self.machine.polled_keys[*key])
+                            .map(|key| format!("{:X}", key))
+                            .collect();
+
+                        if polled.is_empty() {
+                            ui.text_disabled("No key polled");
+                        } else {
+                            ui.text(format!("Polled (EX9E/EXA1): {}", polled.join(" ")));
+                        }
+                    }
+
+                    ui.separator();
+
+                    // the rom keeps its own keys, layouts are for everything else
+                    if self.rom_keys.is_none() {
+                        let names: Vec<String> = self.bindings.names().map(String::from).collect();
+
+                        ui.text("Layout:");
+                        for name in names.iter() {
+                            ui.same_line(0.0);
+                            let active = name == self.bindings.active();
+                            if ui.radio_button_bool(&im_str!("{}##layout", name), active) {
+                                self.bindings.select(name);
+                                self.save_key_bindings();
+                            }
+                        }
+
+                        ui.input_text(im_str!("##layout name"), &mut self.imgui_layout_name)
+                            .build();
+                        ui.same_line(0.0);
+                        if ui.small_button(im_str!("save as")) {
+                            let name = self.imgui_layout_name.to_str().trim().to_string();
+
+                            if !name.is_empty() {
+                                self.bindings.save_as(&name);
+                                self.imgui_layout_name.clear();
+                                self.save_key_bindings();
+                            }
+                        }
+                        ui.same_line(0.0);
+                        if ui.small_button(im_str!("reset")) {
+                            self.bindings.reset_active();
+                            self.save_key_bindings();
+                        }
+                    }
+
+                    if self.rom_hash.is_some() {
+                        let mut own_keys = self.rom_keys.is_some();
+                        if ui.checkbox(im_str!("Keys for this rom only"), &mut own_keys) {
+                            self.set_rom_keys(own_keys);
+                        }
+                    }
+
+                    ui.separator();
+
+                    // what the game uses the keys for, from the rom database
+                    let hints = self
+                        .rom_hash
+                        .as_ref()
+                        .and_then(|hash| self.database.get(hash))
+                        .map(|info| info.keys.clone())
+                        .unwrap_or_default();
+
+                    for key in 0..16 {
+                        let label = format!("{:X}:", key);
+                        if self.machine.keys[key] {
+                            ui.text_colored([0.0, 1.0, 0.0, 1.0], label);
+                        } else {
+                            ui.text(label);
+                        }
+
+                        // clicking a bound key or button removes it
+                        for scancode in self.key_map().keys(key).to_vec() {
+                            ui.same_line(0.0);
+                            if ui.small_button(&im_str!("{}##{}", scancode.name(), key)) {
+                                self.key_map_mut().unbind(key, scancode);
+                                self.save_key_bindings();
+                            }
+                        }
+
+                        for input in self.key_map().pad(key).to_vec() {
+                            ui.same_line(0.0);
+                            if ui.small_button(&im_str!("pad {}##{}", input.name(), key)) {
+                                self.key_map_mut().unbind_pad(key, input);
+                                self.save_key_bindings();
+                            }
+                        }
+
+                        ui.same_line(0.0);
+                        if self.rebinding == Some(key) {
+                            if ui.small_button(&im_str!("press a key or button##{}", key)) {
+                                self.rebinding = None;
+                            }
+                        } else if ui.small_button(&im_str!("+##{}", key)) {
+                            self.rebinding = Some(key);
+                        }
+
+                        for (name, _) in hints.iter().filter(|(_, hint)| *hint as usize == key) {
+                            ui.same_line(0.0);
+                            ui.text_disabled(name);
+                        }
+                    }
+
+                    ui.separator();
+
+                    if controllers.is_empty() {
+                        ui.text_disabled("No game controller");
+                    }
+
+                    for name in controllers.iter() {
+                        ui.text(format!("Controller: {}", name));
+                    }
+                });
+            }
+
+            if let Some(fault) = self.fault {
+                imgui::Window::new(im_str!("Fault"))
+                    .collapsible(false)
+                    .position(
+                        [(width / 3) as f32, (height / 6) as f32],
+                        imgui::Condition::Appearing,
+                    )
+                    .size(
+                        [(width / 3) as f32, (height * 2 / 3) as f32],
+                        imgui::Condition::Appearing,
+                    )
+                    .build(ui, || {
+                        let pc = self.machine.pc as usize;
+
+                        ui.text_colored([1.0, 0.0, 0.0, 1.0], fault.to_string());
+                        ui.text(format!("at {}", self.symbols.describe(pc as u16)));
+
+                        // continue retries the instruction, change its policy in settings first
+                        // both go back to running or paused, whatever it was when the fault hit
+                        if ui.small_button(im_str!("continue")) {
+                            self.fault = None;
+                        }
+                        ui.same_line(0.0);
+                        if ui.small_button(im_str!("skip")) {
+                            self.fault = None;
+                            self.machine.skip_instruction();
+                        }
+                        ui.same_line(0.0);
+                        if ui.small_button(im_str!("reset")) {
+                            self.reset_state();
+                        }
+
+                        ui.separator();
+                        ui.text("Disassembly:");
+
+                        for address in
+                            (pc.saturating_sub(8)..(pc + 10).min(MEMORY_SIZE - 1)).step_by(2)
+                        {
+                            let upper = self.machine.memory[address];
+                            let lower = self.machine.memory[address + 1];
+
+                            let string = format!(
+                                "{:#x}: {:02X} {:02X}  {}",
+                                address,
+                                upper,
+                                lower,
+                                disassemble_with_symbols(upper, lower, &self.symbols)
+                                    .unwrap_or_default()
+                            );
+
+                            if address == pc {
+                                ui.text_colored([1.0, 0.0, 0.0, 1.0], string);
+                            } else {
+                                ui.text(string);
+                            }
+                        }
+
+                        ui.separator();
+                        ui.text("Registers:");
+
+                        for (index, reg) in self.machine.v.iter().enumerate() {
+                            ui.text(format!("V{:x}: {:#04x}", index, reg));
+                            if index % 4 != 3 {
+                                ui.same_line(0.0);
+                            }
+                        }
+                        ui.text(format!(
+                            "I: {:#x}  SP: {:#x}  DT: {:#x}  ST: {:#x}",
+                            self.machine.r_address,
+                            self.machine.sp,
+                            self.machine.r_delay_timer,
+                            self.machine.r_sound_timer
+                        ));
+
+                        ui.separator();
+                        ui.text("Stack:");
+
+                        for (index, address) in self.machine.stack.iter().enumerate().rev() {
+                            let string =
+                                format!("{:x}: {}", index, self.symbols.describe(*address));
+
+                            // entries above the stack pointer are stale
+                            if index < self.machine.sp {
+                                ui.text(string);
+                            } else {
+                                ui.text_disabled(string);
+                            }
+                        }
+
+                        ui.separator();
+                        ui.text("Recently Executed:");
+
+                        for entry in self.machine.history.iter().rev().take(RECENT_INSTRUCTIONS) {
+                            let [upper, lower] = entry.opcode.to_be_bytes();
+
+                            ui.text(format!(
+                                "{}  {:04X}  {}",
+                                self.symbols.describe(entry.registers.pc),
+                                entry.opcode,
+                                disassemble_with_symbols(upper, lower, &self.symbols)
+                                    .unwrap_or_default()
+                            ));
+                        }
+                    });
+            }
+
+            let mut popup_id = Default::default();
+
+            if !self.imgui_error_message.is_empty() {
+                // BAD!!
+                // give every different sized error message a unique id
+                // otherwise imgui doesnt render it properly
+                popup_id = unsafe {
+                    ImString::from_utf8_unchecked(
+                        self.imgui_error_message.len().to_ne_bytes().to_vec(),
+                    )
+                };
+                ui.open_popup(&popup_id);
+            }
+
+            ui.popup_modal(&popup_id)
+                .scroll_bar(false)
+                .movable(false)
+                .resizable(false)
+                .title_bar(false)
+                .build(|| {
+                    ui.text(&self.imgui_error_message);
+
+                    if ui.button(im_str!("OK"), [0.0, 0.0]) {
+                        self.imgui_error_message = String::new();
 
                         ui.close_current_popup();
                     }
                 });
         });
 
+        self.view = windows;
+
+        self.renderer = Some(renderer); // dirty hack v2
+
         if self.vsync_open != vsync_open {
-            renderer
-                .video_subsys
-                .gl_set_swap_interval(if vsync_open {
-                    SwapInterval::VSync
-                } else {
-                    SwapInterval::Immediate
-                })
-                .unwrap();
-
-            self.vsync_open = vsync_open;
+            self.set_vsync(vsync_open);
         }
 
-        self.renderer = Some(renderer); // dirty hack v2
+        // dont write the file for every step of a slider that is being dragged
+        if !editing {
+            self.save_settings();
+        }
+
+        if toggle_play_mode {
+            self.set_play_mode(true);
+        }
 
         // this function may use self.renderer so
         // call it after moving renderer to self.renderer
         if run_next_opcode {
-            self.run_next_opcode();
+            self.step();
         }
-    }
 
-    // helper functions
-    fn is_pc_valid(&self) -> bool {
-        self.rom_loaded && self.pc < MEMORY_SIZE as u16
+        if run_previous_opcode {
+            self.step_back();
+        }
     }
 
+    // helper functions
     fn reset_state(&mut self) {
-        self.v = [0; 16];
-        self.stack = [0; 16];
-        self.sp = 0;
-        self.pc = 0x200;
-        self.r_address = 0;
-        self.r_delay_timer = 0;
-        self.r_sound_timer = 0;
-        self.delay_tick = self.dt_interval;
-        self.sound_tick = self.st_interval;
-
-        self.rom_loaded = false;
-        self.waiting_key_input = false;
-
-        self.width = WIDTH;
-        self.height = HEIGHT;
+        self.machine.reset_state();
 
-        self.current_rom_path = PathBuf::new();
+        self.machine.history.clear();
 
-        self.beeper.device.pause();
-    }
+        self.breakpoint_hit = None;
+        self.fault = None;
 
-    fn clear_screen(&mut self) {
-        self.screen_buffer.fill(self.color_off);
-    }
+        self.current_rom_path = PathBuf::new();
+        self.rom_hash = None;
+        self.rom_keys = None;
+        self.rebinding = None;
+        self.analysis = None;
 
-    fn is_key_pressed(&self, key: Scancode) -> bool {
-        self.renderer
-            .as_ref()
-            .unwrap()
-            .event_pump
-            .keyboard_state()
-            .is_scancode_pressed(key)
+        self.beeper.set_beeping(false);
     }
 
+    // execution faults go to the fault view, this is only for errors outside of the machine
     fn show_error(&mut self, message: String) {
         self.imgui_error_message = message;
-        self.rom_loaded = false;
     }
 
-    fn open_rom(&mut self, path: impl AsRef<Path>) {
-        let rom = match fs::read(path.as_ref()) {
-            Ok(rom) => rom,
-            Err(err) => {
-                self.show_error(format!("{}\npath: {:?}", err, path.as_ref()));
-                return;
+    // play mode hides the debug windows and switches to fullscreen
+    fn set_play_mode(&mut self, on: bool) {
+        let renderer = self.renderer.as_mut().unwrap();
+
+        let fullscreen = match (on, self.play_mode) {
+            (true, None) => {
+                self.play_mode = Some(renderer.is_fullscreen());
+                true
+            }
+            (false, Some(fullscreen)) => {
+                self.play_mode = None;
+                fullscreen
             }
+            _ => return,
         };
 
-        let end = 0x200 + rom.len();
-
-        if end > MEMORY_SIZE {
-            self.show_error(format!(
-                "invalid rom\nrom size({}) cannot exceed {} bytes",
-                rom.len(),
-                MEMORY_SIZE - 0x200
-            ));
-            return;
+        if let Err(err) = renderer.set_fullscreen(fullscreen) {
+            self.show_error(err);
         }
+    }
+
+    fn set_vsync(&mut self, on: bool) {
+        let interval = if on {
+            SwapInterval::VSync
+        } else {
+            SwapInterval::Immediate
+        };
 
-        self.memory[0x200..end].copy_from_slice(&rom);
-        self.memory[end..].fill(0);
+        match self
+            .renderer
+            .as_ref()
+            .unwrap()
+            .video_subsys
+            .gl_set_swap_interval(interval)
+        {
+            Ok(()) => self.vsync_open = on,
+            Err(err) => self.show_error(err),
+        }
+    }
 
-        self.reset_state();
+    // values of the settings window
+    fn current_settings(&mut self) -> Settings {
+        let (volume, frequency) = {
+            let callback = self.beeper.device.lock();
+            (callback.volume, callback.freq)
+        };
 
-        self.rom_loaded = true;
+        Settings {
+            config: self.machine.config,
 
-        self.clear_screen();
+            // pausing is not a setting, keep the last speed
+            cycles_per_frame: match self.cycles_per_frame {
+                0 => self.applied_settings.cycles_per_frame,
+                cycles => cycles,
+            },
+            dt_interval: self.machine.dt_interval,
+            st_interval: self.machine.st_interval,
 
-        self.current_rom_path = path.as_ref().to_owned();
-    }
+            color_on: self.machine.color_on,
+            color_off: self.machine.color_off,
 
-    fn unkown_instruction(&mut self) {
-        let upper = self.memory[self.pc as usize - 2];
-        let lower = self.memory[self.pc as usize - 1];
+            vsync: self.vsync_open,
+            volume,
+            frequency,
 
-        self.show_error(format!(
-            "unkown instruction\nopcode = {:02X} {:02X}",
-            upper, lower
-        ));
+            view: self.view,
+        }
     }
 
-    fn push_pc(&mut self) {
-        if self.sp >= self.stack.len() {
-            self.show_error("cannot push the stack\nstack overflow".to_string());
+    // writes the settings files if anything in the settings window changed since the last save
+    // quirks, speed and palette go to the open rom, everything else is global
+    // only the changed values are written, options of the command line stay out of the files
+    fn save_settings(&mut self) {
+        let settings = self.current_settings();
+
+        if settings == self.applied_settings {
             return;
         }
 
-        self.stack[self.sp] = self.pc;
+        // only report a failing save once
+        let previous = mem::replace(&mut self.applied_settings, settings);
 
-        self.sp += 1;
-    }
+        let rom_changed = Settings::default().with_rom_settings(&settings)
+            != Settings::default().with_rom_settings(&previous);
 
-    fn pop_pc(&mut self) {
-        if self.sp == 0 {
-            self.show_error(
-                "cannot pop the stack\ntryed to pop the stack before pushing it".to_string(),
-            );
-            return;
-        }
+        if let Some(hash) = self.rom_hash.clone() {
+            if rom_changed {
+                let stored = self
+                    .stored_rom_settings(&hash)
+                    .with_changes(&previous, &settings);
 
-        self.sp -= 1;
-
-        self.pc = self.stack[self.sp];
-    }
+                let name = self.rom_name();
+                self.rom_settings.set(&hash, &name, &stored);
 
-    // xor pixel to given locations and return if any pixel is setted off
-    fn set_pixel(&mut self, mut x: usize, mut y: usize, value: u8) -> bool {
-        if x >= self.width {
-            if self.config.draw_behaviour {
-                x = x % self.width;
-            }
-            // wrap around
-            else {
-                return false;
+                if let Err(err) = self.rom_settings.save() {
+                    self.show_error(err);
+                }
             }
         }
 
-        if y >= self.height {
-            if self.config.draw_behaviour {
-                y = y % self.height;
-            }
-            // wrap around
-            else {
-                return false;
-            }
+        let file = match self.settings {
+            Some(file) => file,
+            None => return,
+        };
+
+        let mut global = file.with_changes(&previous, &settings);
+
+        if self.rom_hash.is_some() {
+            global = global.with_rom_settings(&file);
         }
 
-        if value == 0 {
-            false
-        } else {
-            let pixel = &mut self.screen_buffer[y * S_WIDTH + x];
+        if global != file {
+            self.settings = Some(global);
 
-            if *pixel == self.color_off {
-                *pixel = self.color_on;
-                false
-            } else {
-                *pixel = self.color_off;
-                true
+            if let Err(err) = global.save() {
+                self.show_error(err);
             }
         }
     }
 
-    // draw sprite to screen_buffer and if there is a collision set vf to 1 otherwise set vf to 0
-    fn draw_sprite(&mut self, x: usize, y: usize, width: usize, height: usize) {
-        if self.r_address as usize + height > MEMORY_SIZE {
-            self.show_error(format!(
-                "cannot draw sprite\ninvalid address register = {:#x}",
-                self.r_address
-            ));
-            return;
+    // key bindings of the open rom if it has its own, the active layout otherwise
+    fn key_map(&self) -> &KeyMap {
+        match self.rom_keys.as_ref() {
+            Some(key_map) => key_map,
+            None => self.bindings.active_map(),
+        }
+    }
+
+    fn key_map_mut(&mut self) -> &mut KeyMap {
+        match self.rom_keys.as_mut() {
+            Some(key_map) => key_map,
+            None => self.bindings.active_map_mut(),
         }
+    }
 
-        let mut collision = false;
+    // writes whichever bindings are in use, the rom ones or the layouts
+    fn save_key_bindings(&mut self) {
+        let result = match (self.rom_hash.as_ref(), self.rom_keys.as_ref()) {
+            (Some(hash), Some(keys)) => {
+                let name = self.rom_name();
+                self.rom_settings.set_keys(hash, &name, Some(keys));
+                self.rom_settings.save()
+            }
+            _ => self.bindings.save(),
+        };
 
-        for j in 0..height * width {
-            let i = j / width;
-            let t = j % width;
+        if let Err(err) = result {
+            self.show_error(err);
+        }
+    }
 
-            let byte = self.memory[self.r_address as usize + i * (width / 8) + (t / 8)];
+    // gives the open rom a copy of the active layout, or goes back to the layouts
+    fn set_rom_keys(&mut self, own_keys: bool) {
+        let hash = match self.rom_hash.as_ref() {
+            Some(hash) => hash,
+            None => return,
+        };
 
-            // sprites are stored in big endian format
-            let color = (byte >> (7 - (t % 8))) & 0x1;
+        self.rom_keys = if own_keys {
+            Some(self.bindings.active_map().clone())
+        } else {
+            None
+        };
 
-            collision |= self.set_pixel(x + t, y + i, color);
-        }
+        let name = self.rom_name();
+        self.rom_settings
+            .set_keys(hash, &name, self.rom_keys.as_ref());
 
-        self.v[0xF] = collision as u8;
+        if let Err(err) = self.rom_settings.save() {
+            self.show_error(err);
+        }
     }
 
-    // run next instruction
-    fn run_next_opcode(&mut self) {
-        let upper = self.memory[self.pc as usize];
-        let lower = self.memory[self.pc as usize + 1];
+    fn rom_name(&self) -> String {
+        self.current_rom_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
 
-        self.pc += 2;
+    // switches to the remembered quirks, speed and palette of the open rom,
+    // unknown roms use the global ones
+    fn apply_rom_settings(&mut self) {
+        let hash = match self.rom_hash.clone() {
+            Some(hash) => hash,
+            None => return,
+        };
 
-        let nibbles = [
-            ((upper >> 4) & 0xF) as usize,
-            (upper & 0xF) as usize,
-            ((lower >> 4) & 0xF) as usize,
-            (lower & 0xF) as usize,
-        ];
+        let settings = self.stored_rom_settings(&hash);
 
-        let x = nibbles[1];
-        let y = nibbles[2];
+        self.machine.config = settings.config;
+        self.machine.color_on = settings.color_on;
+        self.machine.color_off = settings.color_off;
 
-        let addr = (((upper & 0xF) as u16) << 8) | lower as u16;
+        if self.cycles_per_frame == 0 {
+            self.paused_cycles = settings.cycles_per_frame;
+        } else {
+            self.cycles_per_frame = settings.cycles_per_frame;
+        }
 
-        // opcodes marked with * are new SuperChip instructions
+        self.applied_settings = self.applied_settings.with_rom_settings(&settings);
+    }
 
-        match nibbles[0] {
-            0x0 => {
-                match lower {
-                    0xE0 => self.clear_screen(), // 00E0 -> CLS
-                    0xEE => self.pop_pc(),       // 00EE -> RET
-                    0xFE =>
-                    // 00FE* -> LOW
-                    {
-                        // switch to low resolution mode (64x32)
-                        self.clear_screen();
+    // the settings file with the rom database and roms.toml on top, without the command line
+    fn stored_rom_settings(&mut self, hash: &str) -> Settings {
+        let settings = self.settings.unwrap_or_default();
 
-                        self.width = WIDTH;
-                        self.height = HEIGHT;
-                    }
-                    0xFF =>
-                    // 00FF* -> HIGH
-                    {
-                        // switch to high resolution mode (128x64)
-                        self.clear_screen();
+        // the rom database knows the platform, changes made for the rom override it
+        let base = match self.database.get(hash) {
+            Some(info) => info.apply(settings),
+            None => settings,
+        };
 
-                        self.width = S_WIDTH;
-                        self.height = S_HEIGHT;
-                    }
-                    0xFD => self.reset_state(), // 00FD* -> EXIT
-                    0xFB =>
-                    // 00FB* -> SCR
-                    {
-                        // scroll right 4 pixels
-                        for i in 0..self.height {
-                            for t in (0..self.width).rev() {
-                                self.screen_buffer[i * S_WIDTH + t] = if t < 4 {
-                                    self.color_off
-                                } else {
-                                    self.screen_buffer[i * S_WIDTH + t - 4]
-                                };
-                            }
-                        }
-                    }
-                    0xFC =>
-                    // 00FC* -> SCL
-                    {
-                        // scroll left 4 pixels
-                        for i in 0..self.height {
-                            for t in 0..self.width {
-                                self.screen_buffer[i * S_WIDTH + t] = if t >= self.width - 4 {
-                                    self.color_off
-                                } else {
-                                    self.screen_buffer[i * S_WIDTH + t + 4]
-                                };
-                            }
-                        }
-                    }
-                    n if n == 0xC0 | nibbles[3] as u8 => {
-                        // scroll down 0 to 15 pixels
-                        for t in 0..self.width {
-                            for i in (0..self.height).rev() {
-                                self.screen_buffer[i * S_WIDTH + t] = if i < nibbles[3] {
-                                    self.color_off
-                                } else {
-                                    self.screen_buffer[(i - nibbles[3]) * S_WIDTH + t]
-                                };
-                            }
-                        }
-                    }
-                    _ => self.unkown_instruction(),
-                }
+        match self.rom_settings.get(hash, &base) {
+            Ok(settings) => settings.unwrap_or(base),
+            Err(err) => {
+                self.show_error(err);
+                base
             }
-            0x1 => self.pc = addr,                    // 1NNN -> JP addr
-            0xB => self.pc = addr + self.v[0] as u16, // BNNN -> JP V0, addr
-
-            0x2 => {
-                self.push_pc();
-                self.pc = addr;
-            } // 2NNN -> CALL addr
+        }
+    }
 
-            0x3 => {
-                if self.v[x] == lower {
-                    self.pc += 2
-                }
-            } // 3XNN -> SE  Vx, byte
-            0x4 => {
-                if self.v[x] != lower {
-                    self.pc += 2
-                }
-            } // 4XNN -> SNE Vx, byte
-            0x5 => {
-                if self.v[x] == self.v[y] {
-                    self.pc += 2
-                }
-            } // 5XY0 -> SE  Vx, Vy
-            0x9 => {
-                if self.v[x] != self.v[y] {
-                    self.pc += 2
-                }
-            } // 9XY0 -> SNE Vx, Vy
-
-            0x6 => self.v[x] = lower, // 6XNN -> LD Vx, byte
-            0x7 => self.v[x] = self.v[x].wrapping_add(lower), // 7XNN -> ADD Vx, byte
-
-            0x8 => {
-                match nibbles[3] {
-                    0x0 => self.v[x] = self.v[y],  // 8XY0 -> LD Vx, Vy
-                    0x1 => self.v[x] |= self.v[y], // 8XY1 -> OR Vx, Vy
-                    0x2 => self.v[x] &= self.v[y], // 8XY2 -> AND Vx, Vy
-                    0x3 => self.v[x] ^= self.v[y], // 8XY3 -> XOR Vx, Vy
-                    0x4 =>
-                    // 8XY4 -> ADD Vx, Vy
-                    {
-                        let result = self.v[x] as u16 + self.v[y] as u16;
-                        self.v[x] = (result % 256) as u8;
-                        self.v[0xF] = (result > 0xFF) as u8;
-                    }
-                    0x5 =>
-                    // 8XY5 -> SUB  Vx, Vy
-                    {
-                        let vf = (self.v[x] >= self.v[y]) as u8;
-                        self.v[x] = self.v[x].wrapping_sub(self.v[y]);
-                        self.v[0xF] = vf;
-                    }
-                    0x7 =>
-                    // 8XY7 -> SUBN Vx, Vy
-                    {
-                        let vf = (self.v[y] >= self.v[x]) as u8;
-                        self.v[x] = self.v[y].wrapping_sub(self.v[x]);
-                        self.v[0xF] = vf;
-                    }
-                    0x6 =>
-                    // 8XY6 -> SHR Vx {, Vy}
-                    {
-                        if self.config.shift_behaviour {
-                            self.v[0xF] = self.v[y] & 0x1;
-                            self.v[x] = self.v[y] >> 1;
-                        } else {
-                            self.v[0xF] = self.v[x] & 0x1;
-                            self.v[x] >>= 1;
-                        }
-                    }
-                    0xE =>
-                    // 8XYE -> SHL Vx {, Vy}
-                    {
-                        if self.config.shift_behaviour {
-                            self.v[0xF] = (self.v[y] >> 7) & 0x1;
-                            self.v[x] = self.v[y] << 1;
-                        } else {
-                            self.v[0xF] = (self.v[x] >> 7) & 0x1;
-                            self.v[x] <<= 1;
-                        }
-                    }
-                    _ => self.unkown_instruction(),
-                }
+    fn open_rom(&mut self, path: impl AsRef<Path>) {
+        let rom = match fs::read(path.as_ref()) {
+            Ok(rom) => rom,
+            Err(err) => {
+                self.show_error(format!("{}\npath: {:?}", err, path.as_ref()));
+                return;
             }
-            0xA => self.r_address = addr, // ANNN -> LD I, addr
-            0xC => self.v[x] = thread_rng().gen::<u8>() & lower, // CXNN -> RND Vx, byte
-            0xD =>
-            // DXYN - DXY0*
-            {
-                match nibbles[3] {
-                    0 => self.draw_sprite(self.v[x] as usize, self.v[y] as usize, 16, 16), // DXY0* -> DRW Vx, Vy, 0
-                    height => self.draw_sprite(self.v[x] as usize, self.v[y] as usize, 8, height), // DXYN -> DRW Vx, Vy, nibble
-                }
+        };
+
+        let end = match self.machine.load_rom(&rom) {
+            Ok(end) => end,
+            Err(err) => {
+                self.show_error(err);
+                return;
             }
-            0xE => {
-                match lower {
-                    0x9E => {
-                        if self.is_key_pressed(KEY_MAP[self.v[x] as usize % KEY_MAP.len()]) {
-                            self.pc += 2
-                        }
-                    } // EX9E -> SKP Vx
-                    0xA1 => {
-                        if !self.is_key_pressed(KEY_MAP[self.v[x] as usize % KEY_MAP.len()]) {
-                            self.pc += 2
-                        }
-                    } // EXA1 -> SKNP Vx
-                    _ => self.unkown_instruction(),
-                }
+        };
+
+        self.breakpoint_hit = None;
+        self.fault = None;
+
+        let hash = rom_hash(&rom);
+
+        self.rom_keys = match self.rom_settings.keys(&hash) {
+            Ok(keys) => keys,
+            Err(err) => {
+                self.show_error(err);
+                None
             }
-            0xF => {
-                match lower {
-                    0x07 => self.v[x] = self.r_delay_timer, // FX07 -> LD Vx, DT
-                    0x15 =>
-                    // FX15 -> LD DT, Vx
-                    {
-                        self.r_delay_timer = self.v[x];
-                        self.delay_tick = self.dt_interval;
-                    }
-                    0x18 =>
-                    // FX18 -> LD ST, Vx
-                    {
-                        self.r_sound_timer = self.v[x];
-                        self.sound_tick = self.st_interval;
+        };
+        self.rebinding = None;
 
-                        self.beeper.device.resume()
-                    }
-                    0x0A => self.v[x] = self.wait_key_input() as u8, // FX0A -> LD Vx, K
-                    0x1E => self.r_address += self.v[x] as u16,      // FX1E -> ADD I, Vx
-                    0x29 => self.r_address = self.v[x].min(0xF) as u16 * 5, // FX29 -> LD F, Vx
-                    0x30 =>
-                    // FX30* -> LD HF, Vx
-                    {
-                        self.r_address = SMALL_FONT_SIZE as u16 + self.v[x].min(9) as u16 * 10;
-                    }
-                    0x33 =>
-                    // FX33 -> LD B, Vx
-                    {
-                        self.memory[self.r_address as usize] = (self.v[x] / 100) % 10;
-                        self.memory[self.r_address as usize + 1] = (self.v[x] / 10) % 10;
-                        self.memory[self.r_address as usize + 2] = self.v[x] % 10;
-                    }
-                    0x55 =>
-                    // FX55 -> LD [I], Vx
-                    {
-                        // store v0..vx to memory starting at I (address register)
+        self.current_rom_path = path.as_ref().to_owned();
+        self.rom_hash = Some(hash);
+        self.analysis = Some(Analysis::new(&*self.machine.memory, 0x200, end));
+    }
 
-                        let len = x + 1;
-                        let dest = self.r_address as usize;
+    fn load_symbols(&mut self, path: impl AsRef<Path>) {
+        match SymbolTable::load(path) {
+            Ok(symbols) => self.symbols = symbols,
+            Err(err) => self.show_error(err),
+        }
+    }
 
-                        self.memory[dest..dest + len].copy_from_slice(&self.v[..len]);
-                        if self.config.store_behaviour {
-                            self.r_address += len as u16;
-                        }
-                    }
-                    0x65 =>
-                    // FX65 -> LD Vx, [I]
-                    {
-                        // read v0..vx from memory starting at I (address register)
+    fn export_cfg(&mut self, path: impl AsRef<Path>) {
+        let dot = match self.analysis.as_ref() {
+            Some(analysis) => analysis.to_dot(&*self.machine.memory),
+            None => return,
+        };
 
-                        let len = x + 1;
-                        let src = self.r_address as usize;
+        if let Err(err) = fs::write(path.as_ref(), dot) {
+            self.show_error(format!("{}\npath: {:?}", err, path.as_ref()));
+        }
+    }
 
-                        self.v[..len].copy_from_slice(&self.memory[src..src + len]);
-                        if self.config.store_behaviour {
-                            self.r_address += len as u16;
-                        }
-                    }
-                    0x85 =>
-                    // FX85* -> LD Vx, R
-                    {
-                        let vx = self.v[x].min(7) as usize;
+    fn output_path(&self, extension: &str) -> PathBuf {
+        image::output_path(&self.current_rom_path, extension)
+    }
 
-                        // restore the registers v0..vx
-                        self.v[..vx].copy_from_slice(&self.flag_registers[..vx]);
-                    }
-                    0x75 =>
-                    // FX75* -> LD R, Vx
-                    {
-                        let vx = self.v[x].min(7) as usize;
+    fn save_screenshot(&mut self, path: impl AsRef<Path>) {
+        match image::write_png(path.as_ref(), &self.machine, self.screenshot_scale as usize) {
+            Ok(()) => self.last_screenshot = Some(path.as_ref().to_owned()),
+            Err(err) => self.show_error(err),
+        }
+    }
 
-                        // save v0..vx registers to flag registers
-                        self.flag_registers[..vx].copy_from_slice(&self.v[..vx]);
-                    }
-                    _ => self.unkown_instruction(),
-                }
+    // frames are recorded with the screenshot scale
+    fn start_recording(&mut self, path: impl AsRef<Path>) {
+        match Recorder::create(path, &self.machine, self.screenshot_scale as usize) {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(err) => self.show_error(err),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(err) = recorder.finish() {
+                self.show_error(err);
             }
-            _ => self.unkown_instruction(),
         }
     }
+
+    // run next instruction while paused and log it if tracing is enabled
+    // a trapped fault opens the fault view
+    fn step(&mut self) {
+        if self.trace {
+            print_trace(&self.machine, &self.symbols);
+        }
+
+        self.breakpoint_hit = None;
+
+        if let Err(fault) = self.machine.step() {
+            self.fault = Some(fault);
+        }
+    }
+
+    // undo the last executed instruction, only used while paused
+    fn step_back(&mut self) {
+        if !self.machine.step_back() {
+            return;
+        }
+
+        self.fault = None;
+
+        // dont stop again at a breakpoint that was stepped back onto
+        let pc = self.machine.pc;
+        self.breakpoint_hit = Some(pc).filter(|pc| self.breakpoints.contains(pc));
+    }
+}
+
+// print the instruction at pc, used by the trace option
+#[cfg(feature = "gui")]
+fn print_trace(machine: &Machine, symbols: &SymbolTable) {
+    let pc = machine.pc as usize;
+    let upper = machine.memory[pc % MEMORY_SIZE];
+    let lower = machine.memory[(pc + 1) % MEMORY_SIZE];
+
+    println!(
+        "{}  {:02X}{:02X}  {}",
+        symbols.describe(machine.pc),
+        upper,
+        lower,
+        disassemble_with_symbols(upper, lower, symbols).unwrap_or_default()
+    );
+}
+
+// the keyboard and controllers plus the key held down on the on screen keypad
+#[cfg(feature = "gui")]
+struct ChipInput<'a> {
+    controls: Controls<'a>,
+    held_key: Option<usize>,
+}
+
+#[cfg(feature = "gui")]
+impl Input for ChipInput<'_> {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        self.controls.update_keys(keys);
+
+        if let Some(key) = self.held_key {
+            keys[key] = true;
+        }
+    }
+}
+
+// movable and resizable window placed at pos until the user moves it
+#[cfg(feature = "gui")]
+fn debug_window<'a>(
+    name: &'a ImStr,
+    pos: [f32; 2],
+    size: [f32; 2],
+    condition: imgui::Condition,
+    open: &'a mut bool,
+) -> imgui::Window<'a> {
+    imgui::Window::new(name)
+        .opened(open)
+        .collapsible(false)
+        .position(pos, condition)
+        .size(size, condition)
 }
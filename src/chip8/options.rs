@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use super::{fault::FaultPolicies, utils::Color};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub shift_behaviour: bool, // in 8xy6-8xyE use Vy if this is true otherwise use Vx
+    pub draw_behaviour: bool,  // wrap screen when drawing sprites if this is true
+    pub store_behaviour: bool, // in FX55-FX65 increment I after copying if this is true
+}
+
+impl Config {
+    pub const PROFILES: [&'static str; 2] = ["chip8", "schip"];
+
+    // original Chip8 behaviour
+    pub fn chip8() -> Self {
+        Self {
+            shift_behaviour: true,
+            draw_behaviour: true,
+            store_behaviour: true,
+        }
+    }
+
+    // SuperChip 1.1 behaviour
+    pub fn super_chip() -> Self {
+        Self {
+            shift_behaviour: false,
+            draw_behaviour: false,
+            store_behaviour: false,
+        }
+    }
+
+    pub fn from_profile(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Self::chip8()),
+            "schip" => Some(Self::super_chip()),
+            _ => None,
+        }
+    }
+}
+
+// startup options of the interpreter
+pub struct Options {
+    pub rom_path: Option<PathBuf>,
+    pub symbols_path: Option<PathBuf>,
+
+    pub config: Config,
+    pub fault_policies: FaultPolicies,
+
+    pub cycles_per_frame: u32,
+    pub dt_interval: f64,
+    pub st_interval: f64,
+
+    pub color_on: Color,
+    pub color_off: Color,
+
+    pub vsync: bool,
+    pub volume: f32,    // beeper amplitude, 0 to 1
+    pub frequency: f32, // beeper tone in hz
+
+    pub seed: Option<u64>,   // seed for CXNN, random if None
+    pub history_size: usize, // executed instructions kept for stepping backwards
+    pub start_paused: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            rom_path: None,
+            symbols_path: None,
+
+            config: Config::chip8(),
+            fault_policies: FaultPolicies::default(),
+
+            cycles_per_frame: 60,
+            dt_interval: 1.0 / 60.0,
+            st_interval: 1.0 / 60.0,
+
+            // default colors
+            color_on: Color::new(0xDF, 0xF9, 0xDC),
+            color_off: Color::new(0x0C, 0x42, 0x71),
+
+            vsync: true,
+            volume: 0.2,
+            frequency: 441.0,
+
+            seed: None,
+            history_size: 1024,
+            start_paused: false,
+        }
+    }
+}
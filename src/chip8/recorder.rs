@@ -0,0 +1,236 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use super::{
+    machine::{Machine, S_HEIGHT, S_WIDTH},
+    utils::Color,
+};
+
+const FPS: u32 = 60;
+
+// every frame is recorded at high resolution, low resolution pixels are doubled
+const FRAME_WIDTH: usize = S_WIDTH;
+const FRAME_HEIGHT: usize = S_HEIGHT;
+
+enum Output {
+    // frames are written once the next different frame arrives, so repeated frames
+    // only extend the delay of the previous one
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        palette: [Color; 2],
+        pending: Option<(Vec<u8>, [Color; 2], u32)>, // pixels, palette, first frame
+    },
+    // raw yuv 4:4:4 frames for external encoders
+    Y4m(Box<dyn Write>),
+}
+
+// records the display once per frame at 60 fps
+pub struct Recorder {
+    output: Output,
+    scale: usize, // size of a high resolution pixel
+    frames: u32,  // frames captured so far
+}
+
+impl Recorder {
+    // picks the format from the file extension: gif or anything else for y4m, "-" writes y4m to stdout
+    pub fn create(path: impl AsRef<Path>, machine: &Machine, scale: usize) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        let width = FRAME_WIDTH * scale;
+        let height = FRAME_HEIGHT * scale;
+
+        let io_error = |err: io::Error| format!("{}\npath: {:?}", err, path);
+
+        let is_gif = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some(extension) if extension.eq_ignore_ascii_case("gif")
+        );
+
+        let output = if is_gif {
+            let palette = [machine.color_off, machine.color_on];
+            let file = File::create(path).map_err(io_error)?;
+
+            let mut encoder = gif::Encoder::new(
+                BufWriter::new(file),
+                width as u16,
+                height as u16,
+                &palette_rgb(palette),
+            )
+            .map_err(|err| format!("cannot write gif\n{}", err))?;
+
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(|err| format!("cannot write gif\n{}", err))?;
+
+            Output::Gif {
+                encoder,
+                palette,
+                pending: None,
+            }
+        } else {
+            let mut writer: Box<dyn Write> = if path.to_str() == Some("-") {
+                Box::new(BufWriter::new(io::stdout()))
+            } else {
+                Box::new(BufWriter::new(File::create(path).map_err(io_error)?))
+            };
+
+            writeln!(
+                writer,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, FPS
+            )
+            .map_err(io_error)?;
+
+            Output::Y4m(writer)
+        };
+
+        Ok(Self {
+            output,
+            scale,
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn capture(&mut self, machine: &Machine) -> Result<(), String> {
+        let pixels = self.pixels(machine);
+        let palette = [machine.color_off, machine.color_on];
+        let frame = self.frames;
+
+        self.frames += 1;
+
+        match &mut self.output {
+            Output::Gif {
+                encoder,
+                palette: global_palette,
+                pending,
+            } => {
+                if let Some((previous, previous_palette, _)) = pending.as_ref() {
+                    if *previous == pixels && *previous_palette == palette {
+                        return Ok(());
+                    }
+                }
+
+                if let Some(pending) = pending.take() {
+                    write_gif_frame(encoder, *global_palette, pending, frame, self.scale)?;
+                }
+
+                *pending = Some((pixels, palette, frame));
+
+                Ok(())
+            }
+            Output::Y4m(writer) => {
+                let yuv = [rgb_to_yuv(palette[0]), rgb_to_yuv(palette[1])];
+
+                let mut data = Vec::with_capacity(6 + pixels.len() * 3);
+                data.extend_from_slice(b"FRAME\n");
+
+                // one plane after the other for y, u and v
+                for (off, on) in yuv[0].iter().zip(yuv[1].iter()) {
+                    data.extend(
+                        pixels
+                            .iter()
+                            .map(|pixel| if *pixel == 0 { *off } else { *on }),
+                    );
+                }
+
+                writer
+                    .write_all(&data)
+                    .map_err(|err| format!("cannot write y4m\n{}", err))
+            }
+        }
+    }
+
+    // writes the last frame and flushes the output
+    pub fn finish(self) -> Result<(), String> {
+        match self.output {
+            Output::Gif {
+                mut encoder,
+                palette,
+                pending,
+            } => {
+                if let Some(pending) = pending {
+                    write_gif_frame(&mut encoder, palette, pending, self.frames, self.scale)?;
+                }
+
+                // the encoder writes the trailer when it is dropped
+                Ok(())
+            }
+            Output::Y4m(mut writer) => writer
+                .flush()
+                .map_err(|err| format!("cannot write y4m\n{}", err)),
+        }
+    }
+
+    // palette indices of the scaled frame, 1 is a lit pixel
+    fn pixels(&self, machine: &Machine) -> Vec<u8> {
+        let width = FRAME_WIDTH * self.scale;
+        let height = FRAME_HEIGHT * self.scale;
+
+        let x_scale = self.scale * FRAME_WIDTH / machine.width;
+        let y_scale = self.scale * FRAME_HEIGHT / machine.height;
+
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(machine.is_pixel_on(x / x_scale, y / y_scale) as u8);
+            }
+        }
+
+        pixels
+    }
+}
+
+fn palette_rgb(palette: [Color; 2]) -> Vec<u8> {
+    palette.iter().flat_map(|color| color.as_rgb()).collect()
+}
+
+// bt.601 studio range
+fn rgb_to_yuv(color: Color) -> [u8; 3] {
+    let [r, g, b] = color.as_rgb();
+    let (r, g, b) = (r as f64, g as f64, b as f64);
+
+    let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+    let u = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+    let v = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+// gif delays are in 1/100 seconds, round the frame times so the error doesnt add up
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    global_palette: [Color; 2],
+    (pixels, palette, start): (Vec<u8>, [Color; 2], u32),
+    end: u32,
+    scale: usize,
+) -> Result<(), String> {
+    let centiseconds = |frame: u32| (frame as f64 * 100.0 / FPS as f64).round() as u64;
+    let delay = centiseconds(end) - centiseconds(start);
+
+    let frame = gif::Frame {
+        width: (FRAME_WIDTH * scale) as u16,
+        height: (FRAME_HEIGHT * scale) as u16,
+        delay: delay.min(u16::MAX as u64) as u16,
+        // colors changed while recording
+        palette: if palette == global_palette {
+            None
+        } else {
+            Some(palette_rgb(palette))
+        },
+        buffer: Cow::Owned(pixels),
+        ..gif::Frame::default()
+    };
+
+    encoder
+        .write_frame(&frame)
+        .map_err(|err| format!("cannot write gif\n{}", err))
+}
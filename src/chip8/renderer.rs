@@ -4,12 +4,16 @@ use imgui_opengl_renderer::Renderer as ImguiRenderer;
 use sdl2::{
     event::{Event, WindowEvent},
     keyboard::Keycode,
-    video::{GLContext, GLProfile, SwapInterval},
+    video::{FullscreenType, GLContext, GLProfile, SwapInterval},
     EventPump,
 };
 
 use imgui_sdl2::ImguiSdl2;
 
+use super::{
+    gamepad::{Gamepads, PadInput},
+    utils::config_dir,
+};
 use crate::gl_call;
 
 pub struct Renderer {
@@ -24,6 +28,10 @@ pub struct Renderer {
     imgui_renderer: ImguiRenderer,
 
     pub event_pump: EventPump,
+    pub key_presses: Vec<Keycode>, // keys pressed since the last poll, used for hotkeys
+
+    pub gamepads: Option<Gamepads>, // None without controller support
+    pub pad_presses: Vec<PadInput>, // buttons pressed since the last poll
 
     pub window: sdl2::video::Window,
     pub video_subsys: sdl2::VideoSubsystem,
@@ -51,14 +59,29 @@ impl Renderer {
 
         let event_pump = sdl.event_pump()?;
 
+        // play with the keyboard rather than not at all
+        let gamepads = match Gamepads::new(&sdl) {
+            Ok(gamepads) => Some(gamepads),
+            Err(err) => {
+                eprintln!("warning: no game controller support\n{}", err);
+                None
+            }
+        };
+
         let _gl_context = window.gl_create_context()?;
         gl::load_with(|s| video_subsys.gl_get_proc_address(s) as _);
 
+        // the game view needs framebuffer objects, fail here instead of on the first frame
+        if !gl::GenFramebuffers::is_loaded() || !gl::BlitFramebuffer::is_loaded() {
+            return Err("the OpenGL context does not support framebuffer objects".into());
+        }
+
         video_subsys.gl_set_swap_interval(SwapInterval::VSync)?;
 
         let mut imgui = imgui::Context::create();
 
-        imgui.set_ini_filename(None);
+        // window positions and sizes are kept between sessions
+        imgui.set_ini_filename(config_dir().map(|dir| dir.join("imgui.ini")));
 
         let imgui_renderer =
             ImguiRenderer::new(&mut imgui, |s| video_subsys.gl_get_proc_address(s) as _);
@@ -75,6 +98,9 @@ impl Renderer {
             imgui_sdl,
             imgui_renderer,
             event_pump,
+            key_presses: Vec::new(),
+            gamepads,
+            pad_presses: Vec::new(),
             window,
             video_subsys,
             sdl,
@@ -82,19 +108,31 @@ impl Renderer {
         })
     }
 
+    // returns true when the window is closed, escape is left to the hotkeys like every other key
     pub fn poll_events(&mut self) -> bool {
+        self.key_presses.clear();
+        self.pad_presses.clear();
+
         for event in self.event_pump.poll_iter() {
+            // controllers come and go at any time, imgui doesnt use them
+            if let Some(gamepads) = self.gamepads.as_mut() {
+                if let Some(input) = gamepads.handle_event(&event) {
+                    self.pad_presses.push(input);
+                }
+            }
+
             self.imgui_sdl.handle_event(&mut self.imgui, &event);
             if self.imgui_sdl.ignore_event(&event) {
                 continue;
             }
 
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                Event::Quit { .. } => return true,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
                     ..
-                } => return true,
+                } => self.key_presses.push(keycode),
                 Event::Window { win_event, .. } => match win_event {
                     WindowEvent::Resized(width, height) => {
                         self.window_width = width as u32;
@@ -109,6 +147,18 @@ impl Renderer {
         false
     }
 
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), String> {
+        self.window.set_fullscreen(if fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        })
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.window.fullscreen_state() != FullscreenType::Off
+    }
+
     pub fn clear_screen(&mut self) {
         gl_call!(gl::Clear(gl::COLOR_BUFFER_BIT));
         gl_call!(gl::ClearColor(0.0, 0.0, 0.0, 1.0));
@@ -0,0 +1,428 @@
+use std::{
+    convert::TryFrom,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    keymap::KeyMap,
+    options::{Config, Options},
+    utils::{config_dir, Color},
+    view::View,
+};
+
+const SETTINGS_FILE: &str = "settings.toml";
+const ROMS_FILE: &str = "roms.toml";
+
+// values of the settings window kept between sessions
+// loaded before the command line is parsed so options given there win
+#[derive(Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub config: Config,
+
+    pub cycles_per_frame: u32,
+    pub dt_interval: f64,
+    pub st_interval: f64,
+
+    pub color_on: Color,
+    pub color_off: Color,
+
+    pub vsync: bool,
+    pub volume: f32,
+    pub frequency: f32,
+
+    pub view: View, // open debug windows
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_options(&Options::default())
+    }
+}
+
+impl Settings {
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            config: options.config,
+
+            cycles_per_frame: options.cycles_per_frame,
+            dt_interval: options.dt_interval,
+            st_interval: options.st_interval,
+
+            color_on: options.color_on,
+            color_off: options.color_off,
+
+            vsync: options.vsync,
+            volume: options.volume,
+            frequency: options.frequency,
+
+            view: View::default(),
+        }
+    }
+
+    pub fn apply(&self, options: &mut Options) {
+        options.config = self.config;
+
+        options.cycles_per_frame = self.cycles_per_frame;
+        options.dt_interval = self.dt_interval;
+        options.st_interval = self.st_interval;
+
+        options.color_on = self.color_on;
+        options.color_off = self.color_off;
+
+        options.vsync = self.vsync;
+        options.volume = self.volume;
+        options.frequency = self.frequency;
+    }
+
+    // settings.toml in the user config directory
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(SETTINGS_FILE))
+    }
+
+    // the user settings, defaults if the file doesnt exist yet
+    pub fn load() -> Result<Self, String> {
+        match Self::path() {
+            Some(path) => Self::load_from(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("cannot find the user config directory")?;
+
+        self.save_to(path)
+    }
+
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, String> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(text) => {
+                Self::from_toml(&text).map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("{}\npath: {:?}", err, path.as_ref())),
+        }
+    }
+
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_toml())
+            .map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+    }
+
+    // missing keys keep their default value
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let value: toml::Value = text
+            .parse()
+            .map_err(|err| format!("invalid settings file\n{}", err))?;
+
+        let table = value.as_table().ok_or("invalid settings file")?;
+
+        let mut settings = Self::default();
+        settings.update(table)?;
+
+        Ok(settings)
+    }
+
+    // every value that differs between old and new is taken from new, the rest stays
+    // used to save what the settings window changed without the options of the command line
+    pub fn with_changes(mut self, old: &Settings, new: &Settings) -> Self {
+        macro_rules! change {
+            ($($field:ident).+) => {
+                if old.$($field).+ != new.$($field).+ {
+                    self.$($field).+ = new.$($field).+;
+                }
+            };
+        }
+
+        change!(config.shift_behaviour);
+        change!(config.draw_behaviour);
+        change!(config.store_behaviour);
+
+        change!(cycles_per_frame);
+        change!(dt_interval);
+        change!(st_interval);
+
+        change!(color_on);
+        change!(color_off);
+
+        change!(vsync);
+        change!(volume);
+        change!(frequency);
+
+        change!(view);
+
+        self
+    }
+
+    // the quirks, speed and palette of rom, they are remembered for every rom
+    pub fn with_rom_settings(mut self, rom: &Settings) -> Self {
+        self.config = rom.config;
+        self.cycles_per_frame = rom.cycles_per_frame;
+        self.color_on = rom.color_on;
+        self.color_off = rom.color_off;
+
+        self
+    }
+
+    // overwrites the settings that are in table
+    fn update(&mut self, table: &toml::value::Table) -> Result<(), String> {
+        for (key, value) in table.iter() {
+            let invalid = || format!("invalid value {} for {}", value, key);
+
+            match key.as_str() {
+                "cycles_per_frame" => {
+                    self.cycles_per_frame = value
+                        .as_integer()
+                        .and_then(|n| u32::try_from(n).ok())
+                        .ok_or_else(invalid)?
+                }
+                "delay_interval" => self.dt_interval = as_float(value).ok_or_else(invalid)?,
+                "sound_interval" => self.st_interval = as_float(value).ok_or_else(invalid)?,
+                "foreground" => self.color_on = as_color(value).ok_or_else(invalid)?,
+                "background" => self.color_off = as_color(value).ok_or_else(invalid)?,
+                "vsync" => self.vsync = value.as_bool().ok_or_else(invalid)?,
+                "volume" => self.volume = as_float(value).ok_or_else(invalid)? as f32,
+                "frequency" => self.frequency = as_float(value).ok_or_else(invalid)? as f32,
+                "quirks" => {
+                    for (quirk, value) in value.as_table().ok_or_else(invalid)?.iter() {
+                        let enabled = value
+                            .as_bool()
+                            .ok_or_else(|| format!("invalid value {} for {}", value, quirk))?;
+
+                        match quirk.as_str() {
+                            "shift" => self.config.shift_behaviour = enabled,
+                            "wrap" => self.config.draw_behaviour = enabled,
+                            "store" => self.config.store_behaviour = enabled,
+                            _ => return Err(format!("unknown quirk {:?}", quirk)),
+                        }
+                    }
+                }
+                "windows" => {
+                    for (window, value) in value.as_table().ok_or_else(invalid)?.iter() {
+                        let shown = value
+                            .as_bool()
+                            .ok_or_else(|| format!("invalid value {} for {}", value, window))?;
+
+                        let mut windows = self.view.windows();
+                        let (_, open) = windows
+                            .iter_mut()
+                            .find(|(name, _)| name.eq_ignore_ascii_case(window))
+                            .ok_or_else(|| format!("unknown window {:?}", window))?;
+
+                        **open = shown;
+                    }
+                }
+                _ => return Err(format!("unknown setting {:?}", key)),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        let hex = |color: Color| {
+            let [r, g, b] = color.as_rgb();
+            format!("{:02X}{:02X}{:02X}", r, g, b)
+        };
+
+        let mut view = self.view;
+        let windows: String = view
+            .windows()
+            .iter()
+            .map(|(name, open)| format!("{} = {}\n", name.to_lowercase(), open))
+            .collect();
+
+        format!(
+            "\
+cycles_per_frame = {}
+delay_interval = {:?}
+sound_interval = {:?}
+
+foreground = \"{}\"
+background = \"{}\"
+
+vsync = {}
+volume = {:?}
+frequency = {:?}
+
+[quirks]
+shift = {}
+wrap = {}
+store = {}
+
+[windows]
+{}",
+            self.cycles_per_frame,
+            self.dt_interval,
+            self.st_interval,
+            hex(self.color_on),
+            hex(self.color_off),
+            self.vsync,
+            self.volume,
+            self.frequency,
+            self.config.shift_behaviour,
+            self.config.draw_behaviour,
+            self.config.store_behaviour,
+            windows,
+        )
+    }
+}
+
+// toml integers are accepted where a float is expected
+fn as_float(value: &toml::Value) -> Option<f64> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|n| n as f64))
+}
+
+fn as_color(value: &toml::Value) -> Option<Color> {
+    value.as_str().and_then(Color::from_hex)
+}
+
+// settings of single roms, keyed by the sha-1 of the rom so renamed files keep them
+#[derive(Default)]
+pub struct RomSettings {
+    roms: toml::value::Table,
+}
+
+impl RomSettings {
+    // roms.toml in the user config directory
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(ROMS_FILE))
+    }
+
+    pub fn load() -> Result<Self, String> {
+        match Self::path() {
+            Some(path) => Self::load_from(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("cannot find the user config directory")?;
+
+        self.save_to(path)
+    }
+
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, String> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(text) => {
+                Self::from_toml(&text).map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("{}\npath: {:?}", err, path.as_ref())),
+        }
+    }
+
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_toml())
+            .map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let value: toml::Value = text
+            .parse()
+            .map_err(|err| format!("invalid rom settings file\n{}", err))?;
+
+        match value {
+            toml::Value::Table(roms) => Ok(Self { roms }),
+            _ => Err("invalid rom settings file".to_string()),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        // wrapped in a value so tables are written after plain keys
+        toml::to_string(&toml::Value::Table(self.roms.clone())).unwrap_or_default()
+    }
+
+    // the rom settings of hash on top of settings, None if the rom was never saved
+    pub fn get(&self, hash: &str, settings: &Settings) -> Result<Option<Settings>, String> {
+        let table = match self.roms.get(hash).and_then(|rom| rom.as_table()) {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        let mut settings = *settings;
+
+        // the name is only there to make the file readable, keys are read by keys()
+        let mut table = table.clone();
+        table.remove("name");
+        table.remove("keys");
+
+        settings
+            .update(&table)
+            .map_err(|err| format!("{}\nrom: {}", err, hash))?;
+
+        Ok(Some(settings))
+    }
+
+    // remembers the rom settings of settings for hash, name is usually the file name
+    pub fn set(&mut self, hash: &str, name: &str, settings: &Settings) {
+        let all: toml::Value = settings.to_toml().parse().expect("settings are valid toml");
+
+        let rom = self.entry(hash, name);
+
+        // only keep what belongs to the rom
+        for key in ROM_KEYS.iter() {
+            if let Some(value) = all.get(*key) {
+                rom.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+
+    // key bindings of hash, None if the rom uses the global ones
+    pub fn keys(&self, hash: &str) -> Result<Option<KeyMap>, String> {
+        let keys = match self.roms.get(hash).and_then(|rom| rom.get("keys")) {
+            Some(keys) => keys,
+            None => return Ok(None),
+        };
+
+        let keys = keys
+            .as_table()
+            .ok_or_else(|| format!("invalid keys\nrom: {}", hash))?;
+
+        KeyMap::from_table(keys)
+            .map(Some)
+            .map_err(|err| format!("{}\nrom: {}", err, hash))
+    }
+
+    // None goes back to the global key bindings
+    pub fn set_keys(&mut self, hash: &str, name: &str, keys: Option<&KeyMap>) {
+        match keys {
+            Some(keys) => {
+                let keys = toml::Value::Table(keys.to_table());
+                self.entry(hash, name).insert("keys".to_string(), keys);
+            }
+            None => {
+                if let Some(rom) = self.roms.get_mut(hash).and_then(|rom| rom.as_table_mut()) {
+                    rom.remove("keys");
+                }
+            }
+        }
+    }
+
+    fn entry(&mut self, hash: &str, name: &str) -> &mut toml::value::Table {
+        let rom = self
+            .roms
+            .entry(hash.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+
+        // overwrite anything that isnt a table, it couldnt be read anyway
+        if !rom.is_table() {
+            *rom = toml::Value::Table(toml::value::Table::new());
+        }
+
+        let rom = rom.as_table_mut().unwrap();
+        rom.insert("name".to_string(), toml::Value::String(name.to_string()));
+
+        rom
+    }
+}
+
+// keys of settings.toml that are also saved for every rom
+const ROM_KEYS: [&str; 4] = ["cycles_per_frame", "foreground", "background", "quirks"];
+
+// lowercase hex sha-1 of the rom contents
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1::Sha1::from(rom).digest().to_string()
+}
@@ -0,0 +1,268 @@
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    pixels::{self, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    video::{FullscreenType, Window, WindowContext},
+};
+
+use super::{
+    beeper::Beeper,
+    fault::ExecutionFault,
+    frontend::{self, Audio, Display},
+    gamepad::Gamepads,
+    headless::{EXIT_FAULT, EXIT_OK},
+    image,
+    keymap::{Bindings, Controls},
+    layout::{self, Layout},
+    machine::{Machine, S_HEIGHT, S_WIDTH},
+    options::Options,
+    settings::{rom_hash, RomSettings},
+};
+
+// fallback for machines without OpenGL 3.3, eg. minimal vms or remote desktops
+// draws with sdl's software renderer and replaces the imgui windows with hotkeys:
+//   escape quits, p pauses, f12 saves a screenshot
+
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// run the rom in a window without OpenGL until it is closed, returns the exit code
+pub fn run(options: &Options, scale: u32, fullscreen: bool) -> Result<i32, String> {
+    // theres no settings window to open a rom from later
+    let rom_path = match options.rom_path.clone() {
+        Some(rom_path) => rom_path,
+        None => match tinyfiledialogs::open_file_dialog("Open", "./", None) {
+            Some(rom_path) => rom_path.into(),
+            None => return Ok(EXIT_OK),
+        },
+    };
+
+    let rom = fs::read(&rom_path).map_err(|err| format!("{}\npath: {:?}", err, rom_path))?;
+
+    let mut machine = Machine::new(options);
+    machine.load_rom(&rom)?;
+
+    let sdl = sdl2::init()?;
+    let video_subsys = sdl.video()?;
+
+    let mut window = video_subsys
+        .window("Chip8/SuperChip Interpreter", 64 * scale, 32 * scale)
+        .resizable()
+        .position_centered()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    if fullscreen {
+        if let Err(err) = window.set_fullscreen(FullscreenType::Desktop) {
+            eprintln!("warning: cannot switch to fullscreen\n{}", err);
+        }
+    }
+
+    let canvas = window
+        .into_canvas()
+        .software()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    let texture_creator = canvas.texture_creator();
+    let mut display = CanvasDisplay::new(canvas, &texture_creator)?;
+
+    let mut event_pump = sdl.event_pump()?;
+
+    let mut gamepads = match Gamepads::new(&sdl) {
+        Ok(gamepads) => Some(gamepads),
+        Err(err) => {
+            eprintln!("warning: no game controller support\n{}", err);
+            None
+        }
+    };
+
+    // the keys of the rom if it has its own, the active layout otherwise
+    let bindings = Bindings::load().unwrap_or_else(|err| {
+        eprintln!("warning: ignoring the key bindings file\n{}", err);
+        Bindings::default()
+    });
+
+    let rom_keys = RomSettings::load()
+        .and_then(|roms| roms.keys(&rom_hash(&rom)))
+        .unwrap_or_else(|err| {
+            eprintln!("warning: ignoring the rom settings file\n{}", err);
+            None
+        });
+
+    let key_map = rom_keys.as_ref().unwrap_or_else(|| bindings.active_map());
+
+    // run without sound rather than not at all
+    let mut beeper = match Beeper::new(&sdl) {
+        Ok(beeper) => Some(beeper),
+        Err(err) => {
+            eprintln!("warning: no audio output\n{}", err);
+            None
+        }
+    };
+
+    let mut paused = options.start_paused;
+    let mut fault: Option<ExecutionFault> = None;
+    let mut title = String::new();
+
+    let mut frame_start = Instant::now();
+
+    loop {
+        for event in event_pump.poll_iter() {
+            if let Some(gamepads) = gamepads.as_mut() {
+                gamepads.handle_event(&event);
+            }
+
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => {
+                    beeper.set_beeping(false);
+                    return Ok(if fault.is_some() { EXIT_FAULT } else { EXIT_OK });
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::P),
+                    repeat: false,
+                    ..
+                } => paused = !paused,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => {
+                    let path = image::output_path(&rom_path, "png");
+
+                    match image::write_png(&path, &machine, 1) {
+                        Ok(()) => eprintln!("saved screenshot {:?}", path),
+                        Err(err) => eprintln!("error: {}", err),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let status = status_title(&rom_path, paused, fault.as_ref());
+        if status != title {
+            display.canvas.window_mut().set_title(&status).ok();
+            title = status;
+        }
+
+        // keep the frame rate without vsync
+        if let Some(remaining) = (frame_start + FRAME_TIME).checked_duration_since(Instant::now()) {
+            thread::sleep(remaining);
+        }
+
+        let elapsed = frame_start.elapsed();
+        frame_start = Instant::now();
+
+        if paused || fault.is_some() {
+            beeper.set_beeping(false);
+            display.present(&machine);
+        } else if let Err(err) = frontend::run_frame(
+            &mut machine,
+            options.cycles_per_frame,
+            elapsed.as_secs_f64(),
+            &mut Controls {
+                event_pump: &event_pump,
+                gamepads: gamepads.as_ref(),
+                key_map,
+            },
+            &mut beeper,
+            &mut display,
+        ) {
+            // theres no debugger to inspect it, keep the last frame on screen
+            eprintln!("{}\npc = {:#x}", err, machine.pc);
+            fault = Some(err);
+        }
+
+        if let Some(err) = display.error.take() {
+            return Err(err);
+        }
+    }
+}
+
+fn status_title(rom_path: &Path, paused: bool, fault: Option<&ExecutionFault>) -> String {
+    let name = rom_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("chip8");
+
+    match fault {
+        Some(fault) => format!("{} - {} (escape quits)", name, fault),
+        None if paused => format!("{} - paused", name),
+        None => name.to_string(),
+    }
+}
+
+// streams the display into a texture and scales it to the window
+struct CanvasDisplay<'a> {
+    canvas: Canvas<Window>,
+    texture: Texture<'a>, // high resolution, low resolution only uses the top left quarter
+    error: Option<String>, // draw error, checked after every frame
+}
+
+impl<'a> CanvasDisplay<'a> {
+    fn new(
+        canvas: Canvas<Window>,
+        texture_creator: &'a TextureCreator<WindowContext>,
+    ) -> Result<Self, String> {
+        let texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, S_WIDTH as u32, S_HEIGHT as u32)
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            canvas,
+            texture,
+            error: None,
+        })
+    }
+
+    fn draw(&mut self, machine: &Machine) -> Result<(), String> {
+        let source = Rect::new(0, 0, machine.width as u32, machine.height as u32);
+
+        self.texture.with_lock(source, |buffer, pitch| {
+            for y in 0..machine.height {
+                let row = &mut buffer[y * pitch..];
+
+                for x in 0..machine.width {
+                    row[x * 3..x * 3 + 3].copy_from_slice(&machine.pixel_color(x, y).as_rgb());
+                }
+            }
+        })?;
+
+        // integer scaled and letterboxed with black bars
+        let (width, height) = self.canvas.output_size()?;
+        let view = Layout::default().fit(
+            layout::Rect::new(0, 0, width, height),
+            machine.width,
+            machine.height,
+        );
+
+        let target = Rect::new(view.x, view.y, view.width, view.height);
+
+        self.canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, source, target)?;
+        self.canvas.present();
+
+        Ok(())
+    }
+}
+
+impl Display for CanvasDisplay<'_> {
+    fn present(&mut self, machine: &Machine) {
+        if let Err(err) = self.draw(machine) {
+            self.error = Some(err);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fs,
+    path::Path,
+};
+
+use super::machine::MEMORY_SIZE;
+
+// address <-> label mapping loaded from a symbol file
+// supported formats:
+//  - plain text, one `address name` pair per line, '#' and ';' start comments
+//  - Octo style json, either `{"labels": {"name": address}}` or `{"name": address}`
+#[derive(Default)]
+pub struct SymbolTable {
+    names: BTreeMap<u16, String>,
+    addresses: HashMap<String, u16>,
+}
+
+impl SymbolTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let text = fs::read_to_string(path.as_ref())
+            .map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))?;
+
+        let table = if text.trim_start().starts_with('{') {
+            Self::parse_json(&text)
+        } else {
+            Self::parse_text(&text)
+        };
+
+        table.map_err(|err| format!("{}\npath: {:?}", err, path.as_ref()))
+    }
+
+    pub fn parse_text(text: &str) -> Result<Self, String> {
+        let mut table = Self::default();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split(['#', ';']).next().unwrap_or("");
+            let mut words = line.split_whitespace();
+
+            let (address, name) = match (words.next(), words.next()) {
+                (Some(address), Some(name)) => (address, name),
+                (None, _) => continue,
+                _ => {
+                    return Err(format!(
+                        "invalid symbol file\nline {}: expected `address name`",
+                        line_number + 1
+                    ))
+                }
+            };
+
+            let address = parse_address(address)
+                .filter(|&a| in_memory(a))
+                .ok_or_else(|| {
+                    format!(
+                        "invalid symbol file\nline {}: invalid address {:?}",
+                        line_number + 1,
+                        address
+                    )
+                })?;
+
+            table.insert(address, name);
+        }
+
+        Ok(table)
+    }
+
+    pub fn parse_json(text: &str) -> Result<Self, String> {
+        let json: serde_json::Value =
+            serde_json::from_str(text).map_err(|err| format!("invalid symbol file\n{}", err))?;
+
+        let labels = json
+            .get("labels")
+            .unwrap_or(&json)
+            .as_object()
+            .ok_or("invalid symbol file\nexpected an object of labels")?;
+
+        let mut table = Self::default();
+
+        for (name, value) in labels.iter() {
+            let address = match value {
+                serde_json::Value::Number(number) => {
+                    number.as_u64().and_then(|n| u16::try_from(n).ok())
+                }
+                serde_json::Value::String(string) => parse_address(string),
+                _ => None,
+            }
+            .filter(|&address| in_memory(address));
+
+            match address {
+                Some(address) => table.insert(address, name),
+                None => {
+                    return Err(format!(
+                        "invalid symbol file\ninvalid address for {}: {}",
+                        name, value
+                    ))
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn insert(&mut self, address: u16, name: &str) {
+        // keep the first name when multiple labels share an address
+        self.names
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    // formats an address relative to the closest label before it, eg. "0x2d8 <draw+0x4>"
+    pub fn describe(&self, address: u16) -> String {
+        match self.names.range(..=address).next_back() {
+            Some((&start, name)) if start == address => format!("{:#05x} <{}>", address, name),
+            Some((&start, name)) => {
+                format!("{:#05x} <{}+{:#x}>", address, name, address - start)
+            }
+            None => format!("{:#05x}", address),
+        }
+    }
+
+    // resolves either a label or a hex address
+    pub fn resolve(&self, string: &str) -> Option<u16> {
+        let string = string.trim();
+
+        self.address(string).or_else(|| parse_address(string))
+    }
+}
+
+fn in_memory(address: u16) -> bool {
+    (address as usize) < MEMORY_SIZE
+}
+
+fn parse_address(string: &str) -> Option<u16> {
+    let digits = string
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .trim_start_matches('$');
+
+    u16::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolTable;
+
+    // labels outside of the 4 KiB address space are rejected instead of wrapped
+    #[test]
+    fn labels_must_be_in_memory() {
+        let table =
+            SymbolTable::parse_json(r#"{"labels": {"main": 512, "end": "0xfff"}}"#).unwrap();
+        assert_eq!(table.address("main"), Some(0x200));
+        assert_eq!(table.address("end"), Some(0xfff));
+
+        assert!(SymbolTable::parse_json(r#"{"far": 4096}"#).is_err());
+        assert!(SymbolTable::parse_json(r#"{"wrapped": 66048}"#).is_err());
+        assert!(SymbolTable::parse_text("1000 far").is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    fs,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{self, Print, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use super::{
+    fault::ExecutionFault,
+    frontend::{self, Audio, Display, Input},
+    headless::{EXIT_FAULT, EXIT_OK},
+    keymap::{Bindings, KeyMap},
+    machine::Machine,
+    options::Options,
+    settings::{rom_hash, RomSettings},
+    utils::Color,
+};
+
+// terminals dont report key releases, a press holds the key for this many frames
+// holding a key down keeps it pressed through the terminal's key repeat
+const KEY_HOLD_FRAMES: u32 = 6;
+
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Default)]
+pub struct Tui {
+    pub braille: bool, // 2x4 pixels per character instead of 1x2 half blocks
+}
+
+// run the rom in the terminal until escape is pressed, returns the exit code
+pub fn run(options: &Options, tui: &Tui) -> Result<i32, String> {
+    let rom_path = options
+        .rom_path
+        .as_ref()
+        .ok_or("the terminal frontend requires a rom")?;
+
+    let rom = fs::read(rom_path).map_err(|err| format!("{}\npath: {:?}", err, rom_path))?;
+
+    let mut machine = Machine::new(options);
+    machine.load_rom(&rom)?;
+
+    // the keys of the rom if it has its own, the active layout otherwise
+    let bindings = Bindings::load().unwrap_or_else(|err| {
+        eprintln!("warning: ignoring the key bindings file\n{}", err);
+        Bindings::default()
+    });
+
+    let rom_keys = RomSettings::load()
+        .and_then(|roms| roms.keys(&rom_hash(&rom)))
+        .unwrap_or_else(|err| {
+            eprintln!("warning: ignoring the rom settings file\n{}", err);
+            None
+        });
+
+    let key_map = rom_keys.unwrap_or_else(|| bindings.active_map().clone());
+
+    let mut stdout = io::stdout();
+
+    terminal::enable_raw_mode().map_err(|err| err.to_string())?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)
+        .map_err(|err| err.to_string())?;
+
+    let result = run_loop(&mut machine, options.cycles_per_frame, tui, key_map);
+
+    // restore the terminal before reporting anything
+    execute!(
+        stdout,
+        style::ResetColor,
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    )
+    .ok();
+    terminal::disable_raw_mode().ok();
+
+    match result.map_err(|err| err.to_string())? {
+        Some(fault) => {
+            eprintln!("{}\npc = {:#x}", fault, machine.pc);
+            Ok(EXIT_FAULT)
+        }
+        None => Ok(EXIT_OK),
+    }
+}
+
+// returns the trapped fault if the rom raised one
+fn run_loop(
+    machine: &mut Machine,
+    cycles_per_frame: u32,
+    tui: &Tui,
+    key_map: KeyMap,
+) -> io::Result<Option<ExecutionFault>> {
+    let mut input = TerminalInput {
+        key_map,
+        key_frames: [0; 16],
+        quit: false,
+    };
+    let mut audio = TerminalBell::default();
+    let mut display = TerminalDisplay {
+        braille: tui.braille,
+        last_frame: Vec::new(),
+        last_size: (0, 0),
+        error: None,
+    };
+
+    let mut frame_start = Instant::now();
+
+    loop {
+        input.wait_frame(frame_start + FRAME_TIME)?;
+        frame_start = Instant::now();
+
+        if input.quit {
+            return Ok(None);
+        }
+
+        let result = frontend::run_frame(
+            machine,
+            cycles_per_frame,
+            FRAME_TIME.as_secs_f64(),
+            &mut input,
+            &mut audio,
+            &mut display,
+        );
+
+        if let Some(err) = display.error.take() {
+            return Err(err);
+        }
+
+        if let Err(fault) = result {
+            return Ok(Some(fault));
+        }
+
+        if !machine.is_running() {
+            return Ok(None);
+        }
+    }
+}
+
+struct TerminalInput {
+    key_map: KeyMap,
+    key_frames: [u32; 16], // frames left until a key is released
+    quit: bool,
+}
+
+impl TerminalInput {
+    // handle key presses until the next frame starts
+    fn wait_frame(&mut self, deadline: Instant) -> io::Result<()> {
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            if !event::poll(timeout)? {
+                break;
+            }
+
+            if let Event::Key(KeyEvent { code, modifiers }) = event::read()? {
+                match code {
+                    KeyCode::Esc => self.quit = true,
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.quit = true
+                    }
+                    KeyCode::Char(c) => {
+                        if let Some(key) = self.key_map.key_for_char(c) {
+                            self.key_frames[key] = KEY_HOLD_FRAMES;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Input for TerminalInput {
+    fn update_keys(&mut self, keys: &mut [bool; 16]) {
+        for (pressed, frames) in keys.iter_mut().zip(self.key_frames.iter_mut()) {
+            *pressed = *frames > 0;
+            *frames = frames.saturating_sub(1);
+        }
+    }
+}
+
+// rings the terminal bell when a beep starts
+#[derive(Default)]
+struct TerminalBell {
+    beeping: bool,
+}
+
+impl Audio for TerminalBell {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping && !self.beeping {
+            execute!(io::stdout(), Print('\x07')).ok();
+        }
+
+        self.beeping = beeping;
+    }
+}
+
+struct TerminalDisplay {
+    braille: bool,
+    last_frame: Vec<u8>,
+    last_size: (usize, usize),
+    error: Option<io::Error>, // write error, checked after every frame
+}
+
+impl TerminalDisplay {
+    fn draw(&mut self, machine: &Machine) -> io::Result<()> {
+        let mut stdout = io::stdout();
+
+        // the display only changes size when the resolution changes
+        let size = (machine.width, machine.height);
+        if size != self.last_size {
+            queue!(stdout, terminal::Clear(terminal::ClearType::All))?;
+            self.last_frame.clear();
+            self.last_size = size;
+        }
+
+        let frame = if self.braille {
+            draw_braille(machine)?
+        } else {
+            draw_half_blocks(machine)?
+        };
+
+        if frame != self.last_frame {
+            queue!(stdout, cursor::MoveTo(0, 0))?;
+            stdout.write_all(&frame)?;
+            self.last_frame = frame;
+        }
+
+        stdout.flush()
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn present(&mut self, machine: &Machine) {
+        if let Err(err) = self.draw(machine) {
+            self.error = Some(err);
+        }
+    }
+}
+
+fn term_color(color: Color) -> style::Color {
+    let [r, g, b] = color.as_rgb();
+    style::Color::Rgb { r, g, b }
+}
+
+// every character shows two pixels, the upper one as foreground and the lower one as background
+fn draw_half_blocks(machine: &Machine) -> io::Result<Vec<u8>> {
+    let mut frame = Vec::new();
+
+    let color = |x, y| machine.pixel_color(x, y);
+
+    for y in (0..machine.height).step_by(2) {
+        let mut colors = None;
+
+        for x in 0..machine.width {
+            let pair = (color(x, y), color(x, y + 1));
+
+            // only switch colors when they change
+            if colors != Some(pair) {
+                queue!(
+                    frame,
+                    SetForegroundColor(term_color(pair.0)),
+                    SetBackgroundColor(term_color(pair.1))
+                )?;
+                colors = Some(pair);
+            }
+
+            queue!(frame, Print('▀'))?;
+        }
+
+        queue!(frame, style::ResetColor, Print("\r\n"))?;
+    }
+
+    Ok(frame)
+}
+
+// every character shows 2x4 pixels as braille dots
+fn draw_braille(machine: &Machine) -> io::Result<Vec<u8>> {
+    // dot bits of the braille block, indexed by [y][x]
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    let mut frame = Vec::new();
+
+    queue!(
+        frame,
+        SetForegroundColor(term_color(machine.color_on)),
+        SetBackgroundColor(term_color(machine.color_off))
+    )?;
+
+    for y in (0..machine.height).step_by(4) {
+        for x in (0..machine.width).step_by(2) {
+            let mut bits = 0;
+
+            for (dy, row) in DOTS.iter().enumerate() {
+                for (dx, bit) in row.iter().enumerate() {
+                    if machine.is_pixel_on(x + dx, y + dy) {
+                        bits |= bit;
+                    }
+                }
+            }
+
+            queue!(frame, Print(std::char::from_u32(0x2800 + bits).unwrap()))?;
+        }
+
+        queue!(frame, Print("\r\n"))?;
+    }
+
+    queue!(frame, style::ResetColor)?;
+
+    Ok(frame)
+}
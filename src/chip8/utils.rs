@@ -1,3 +1,6 @@
+#[cfg(any(feature = "gui", feature = "tui"))]
+use std::{fs, path::PathBuf};
+
 use rand::{thread_rng, Rng};
 
 #[repr(C)]
@@ -14,6 +17,23 @@ impl Color {
         Self { r, g, b, a: 0 }
     }
 
+    // parses "RRGGBB" with an optional leading '#'
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim_start_matches('#');
+
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let rgb = u32::from_str_radix(hex, 16).ok()?;
+
+        Some(Self::new(
+            ((rgb >> 16) & 0xFF) as u8,
+            ((rgb >> 8) & 0xFF) as u8,
+            (rgb & 0xFF) as u8,
+        ))
+    }
+
     pub fn rand() -> Self {
         let rand: u32 = thread_rng().gen();
 
@@ -25,6 +45,21 @@ impl Color {
         }
     }
 
+    pub fn as_rgb(&self) -> [u8; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    // linear blend towards other, amount 0 is self and 1 is other
+    pub fn mix(self, other: Color, amount: f32) -> Self {
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+
+        Self::new(
+            channel(self.r, other.r),
+            channel(self.g, other.g),
+            channel(self.b, other.b),
+        )
+    }
+
     pub fn as_array(&self) -> [f32; 3] {
         [
             (self.r as f32) / 255.0,
@@ -42,12 +77,24 @@ impl Color {
     }
 }
 
+// per user directory for settings and window layout, created on first use
+#[cfg(any(feature = "gui", feature = "tui"))]
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = dirs::config_dir()?.join("chip8");
+
+    fs::create_dir_all(&dir).ok()?;
+
+    Some(dir)
+}
+
 #[allow(dead_code)]
+#[cfg(feature = "gui")]
 pub fn clear_gl_errors() {
     while unsafe { gl::GetError() } != gl::NO_ERROR {}
 }
 
 #[allow(dead_code)]
+#[cfg(feature = "gui")]
 pub fn check_gl_errors() {
     loop {
         match unsafe { gl::GetError() } {
@@ -60,21 +107,21 @@ pub fn check_gl_errors() {
 }
 
 #[macro_export]
-#[cfg(debug_assertions)]
+#[cfg(all(feature = "gui", debug_assertions))]
 macro_rules! gl_call {
     ($x: expr) => {{
-        crate::chip8::utils::clear_gl_errors();
+        $crate::utils::clear_gl_errors();
 
         let result = unsafe { $x };
 
-        crate::chip8::utils::check_gl_errors();
+        $crate::utils::check_gl_errors();
 
         result
     }};
 }
 
 #[macro_export]
-#[cfg(not(debug_assertions))]
+#[cfg(all(feature = "gui", not(debug_assertions)))]
 macro_rules! gl_call {
     ($x: expr) => {
         unsafe { $x }
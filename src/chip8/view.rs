@@ -0,0 +1,39 @@
+// debug windows that can be shown or hidden from the View menu
+// which ones are open is kept in the settings file, imgui keeps their positions in its ini file
+#[derive(Clone, Copy, PartialEq)]
+pub struct View {
+    pub settings: bool,
+    pub registers: bool,
+    pub debugger: bool,
+    pub memory: bool,
+    pub keyboard: bool,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            settings: true,
+            registers: true,
+            debugger: true,
+            memory: true,
+            keyboard: true,
+        }
+    }
+}
+
+impl View {
+    pub fn windows(&mut self) -> [(&'static str, &mut bool); 5] {
+        [
+            ("Settings", &mut self.settings),
+            ("Registers", &mut self.registers),
+            ("Debugger", &mut self.debugger),
+            ("Memory", &mut self.memory),
+            ("Keyboard", &mut self.keyboard),
+        ]
+    }
+
+    pub fn any_open(&self) -> bool {
+        let mut view = *self;
+        view.windows().iter().any(|(_, open)| **open)
+    }
+}
@@ -0,0 +1,292 @@
+use std::path::PathBuf;
+
+#[cfg(any(feature = "gui", feature = "tui"))]
+use chip8::settings::Settings;
+#[cfg(feature = "tui")]
+use chip8::tui::Tui;
+use chip8::{
+    fault::{FaultKind, FaultPolicy},
+    headless::Headless,
+    options::{Config, Options},
+    Color,
+};
+#[cfg(feature = "gui")]
+use chip8::{layout::Layout, MENU_BAR_HEIGHT};
+
+pub const USAGE: &str = "\
+Chip8/SuperChip Interpreter
+
+USAGE:
+    chip8 [OPTIONS] [ROM]
+
+OPTIONS:
+    -p, --profile <NAME>         quirk profile: chip8 or schip
+    -c, --cycles <N>             instructions executed per frame
+        --delay-interval <SECS>  seconds between delay timer ticks
+        --sound-interval <SECS>  seconds between sound timer ticks
+        --fg <RRGGBB>            foreground color
+        --bg <RRGGBB>            background color
+        --seed <N>               seed for the random number generator
+        --symbols <FILE>         load labels from a symbol file
+        --history <N>            instructions kept for stepping backwards, 0 disables it
+        --fault <KIND=POLICY>    trap, wrap or ignore a fault, can be repeated
+                                 kinds: stack-overflow, stack-underflow, unknown-opcode,
+                                        memory, font-digit
+    -s, --scale <N>              size of a low resolution pixel in the window
+    -f, --fullscreen             start in fullscreen
+        --software               draw without OpenGL and imgui, used when OpenGL 3.3 is missing
+        --paused                 start with execution paused
+    -h, --help                   print this message
+
+    defaults are read from settings.toml in the user config directory, which the
+    Settings window writes when it changes something
+
+HEADLESS OPTIONS:
+        --headless               run without a window and exit, required by the options below
+        --frames <N>             frames to run, 600 by default
+        --input <FILE>           scripted key presses, `frame keys` per line
+        --screen <FILE>          write the final display as .png, .pbm or ascii art, - for stdout
+        --state <FILE>           write the final registers and memory as json
+        --record <FILE>          record every frame as .gif or raw .y4m video, - for y4m to stdout
+        --cfg <FILE>             write the control flow graph of the rom as graphviz dot
+
+    the settings files and the rom database are not read, a run only depends on the rom and the options
+    exits with 0 on success, 1 if the rom trapped a fault and 2 on invalid usage
+
+TERMINAL OPTIONS:
+        --tui                    draw the display in the terminal with half block characters
+        --braille                draw the display with braille characters, implies --tui
+
+    keys are the letters and digits of the active key bindings, escape quits
+";
+
+pub struct Args {
+    pub options: Options,
+    pub overrides: Overrides, // settings given on the command line, already part of options
+
+    // window options, parsed without the gui feature so the same command lines are valid
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub scale: Option<u32>,
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fullscreen: bool,
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub software: bool, // sdl's software renderer with hotkeys instead of the imgui windows
+
+    pub headless: Option<Headless>, // None when running with a window
+    #[cfg(feature = "tui")]
+    pub tui: Option<Tui>, // Some when running in the terminal
+}
+
+impl Args {
+    // window size that fits the game view with the given scale
+    #[cfg(feature = "gui")]
+    pub fn window_size(&self) -> (u32, u32) {
+        match self.scale {
+            // game view takes the top left quarter of the window below the menu bar
+            Some(scale) => {
+                let (width, height) = Layout::default().area_for(64, 32, scale);
+                (width * 2, (height + MENU_BAR_HEIGHT) * 2)
+            }
+            None => (1024, 720),
+        }
+    }
+
+    // settings from the files under the options of the command line
+    #[cfg(any(feature = "gui", feature = "tui"))]
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        settings.apply(&mut self.options);
+        self.overrides.apply(&mut self.options);
+    }
+}
+
+// settings given on the command line, None where the settings files decide
+#[derive(Default)]
+pub struct Overrides {
+    pub config: Option<Config>,
+    pub cycles_per_frame: Option<u32>,
+    pub dt_interval: Option<f64>,
+    pub st_interval: Option<f64>,
+    pub color_on: Option<Color>,
+    pub color_off: Option<Color>,
+}
+
+impl Overrides {
+    pub fn apply(&self, options: &mut Options) {
+        if let Some(config) = self.config {
+            options.config = config;
+        }
+
+        if let Some(cycles_per_frame) = self.cycles_per_frame {
+            options.cycles_per_frame = cycles_per_frame;
+        }
+
+        if let Some(dt_interval) = self.dt_interval {
+            options.dt_interval = dt_interval;
+        }
+
+        if let Some(st_interval) = self.st_interval {
+            options.st_interval = st_interval;
+        }
+
+        if let Some(color_on) = self.color_on {
+            options.color_on = color_on;
+        }
+
+        if let Some(color_off) = self.color_off {
+            options.color_off = color_off;
+        }
+    }
+}
+
+// options start from the defaults, the settings files are applied by the caller
+// returns Ok(None) when help is requested
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut result = Args {
+        options: Options::default(),
+        overrides: Overrides::default(),
+        scale: None,
+        fullscreen: false,
+        software: false,
+        headless: None,
+        #[cfg(feature = "tui")]
+        tui: None,
+    };
+
+    // headless only options are collected until --headless is seen
+    let mut headless = false;
+    let mut headless_options = Headless::default();
+    let mut headless_flag = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+
+        let options = &mut result.options;
+        let overrides = &mut result.overrides;
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-p" | "--profile" => {
+                let name = value(&arg)?;
+                overrides.config = Some(Config::from_profile(&name).ok_or_else(|| {
+                    format!(
+                        "unknown profile {:?}, expected one of {:?}",
+                        name,
+                        Config::PROFILES
+                    )
+                })?);
+            }
+            "-c" | "--cycles" => {
+                overrides.cycles_per_frame = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "--delay-interval" => overrides.dt_interval = Some(parse_number(&arg, value(&arg)?)?),
+            "--sound-interval" => overrides.st_interval = Some(parse_number(&arg, value(&arg)?)?),
+            "--fg" => overrides.color_on = Some(parse_color(&arg, value(&arg)?)?),
+            "--bg" => overrides.color_off = Some(parse_color(&arg, value(&arg)?)?),
+            "--seed" => options.seed = Some(parse_number(&arg, value(&arg)?)?),
+            "--symbols" => options.symbols_path = Some(PathBuf::from(value(&arg)?)),
+            "--history" => options.history_size = parse_number(&arg, value(&arg)?)?,
+            "--fault" => {
+                let (kind, policy) = parse_fault(&arg, value(&arg)?)?;
+                *options.fault_policies.get_mut(kind) = policy;
+            }
+            "-s" | "--scale" => {
+                let scale = parse_number(&arg, value(&arg)?)?;
+                if scale == 0 {
+                    return Err("scale must be at least 1".to_string());
+                }
+                result.scale = Some(scale);
+            }
+            "-f" | "--fullscreen" => result.fullscreen = true,
+            "--software" => result.software = true,
+            "--paused" => options.start_paused = true,
+            "--headless" => headless = true,
+            "--frames" => {
+                headless_options.frames = parse_number(&arg, value(&arg)?)?;
+                headless_flag.get_or_insert(arg);
+            }
+            "--input" => {
+                headless_options.input_path = Some(PathBuf::from(value(&arg)?));
+                headless_flag.get_or_insert(arg);
+            }
+            "--screen" => {
+                headless_options.screen_path = Some(PathBuf::from(value(&arg)?));
+                headless_flag.get_or_insert(arg);
+            }
+            "--state" => {
+                headless_options.state_path = Some(PathBuf::from(value(&arg)?));
+                headless_flag.get_or_insert(arg);
+            }
+            "--record" => {
+                headless_options.record_path = Some(PathBuf::from(value(&arg)?));
+                headless_flag.get_or_insert(arg);
+            }
+            "--cfg" => {
+                headless_options.cfg_path = Some(PathBuf::from(value(&arg)?));
+                headless_flag.get_or_insert(arg);
+            }
+            #[cfg(feature = "tui")]
+            "--tui" => {
+                result.tui.get_or_insert_with(Tui::default);
+            }
+            #[cfg(feature = "tui")]
+            "--braille" => result.tui.get_or_insert_with(Tui::default).braille = true,
+            #[cfg(not(feature = "tui"))]
+            "--tui" | "--braille" => return Err(format!("{} needs the tui feature", arg)),
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            rom => {
+                if options.rom_path.is_some() {
+                    return Err(format!("unexpected argument {}", rom));
+                }
+                options.rom_path = Some(PathBuf::from(rom));
+            }
+        }
+    }
+
+    result.overrides.apply(&mut result.options);
+
+    if headless {
+        result.headless = Some(headless_options);
+    } else if let Some(flag) = headless_flag {
+        return Err(format!("{} requires --headless", flag));
+    }
+
+    #[cfg(feature = "tui")]
+    if result.headless.is_some() && result.tui.is_some() {
+        return Err("headless and terminal options cannot be combined".to_string());
+    }
+
+    Ok(Some(result))
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for {}", value, name))
+}
+
+fn parse_color(name: &str, value: String) -> Result<Color, String> {
+    Color::from_hex(&value).ok_or_else(|| format!("invalid color {:?} for {}", value, name))
+}
+
+fn parse_fault(name: &str, value: String) -> Result<(FaultKind, FaultPolicy), String> {
+    let invalid = || {
+        format!(
+            "invalid value {:?} for {}, expected KIND=POLICY",
+            value, name
+        )
+    };
+
+    let mut parts = value.splitn(2, '=');
+    let kind = parts.next().ok_or_else(invalid)?;
+    let policy = parts.next().ok_or_else(invalid)?;
+
+    let kind =
+        FaultKind::from_name(kind).ok_or_else(|| format!("unknown fault kind {:?}", kind))?;
+    let policy = FaultPolicy::from_name(policy)
+        .ok_or_else(|| format!("unknown fault policy {:?}", policy))?;
+
+    Ok((kind, policy))
+}
@@ -1,7 +1,240 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod chip8;
+mod cli;
+
+#[cfg(any(feature = "gui", feature = "tui"))]
+use std::fs;
+use std::process;
+
+#[cfg(any(feature = "gui", feature = "tui"))]
+use chip8::{
+    database::Database,
+    settings::{rom_hash, RomSettings, Settings},
+};
+#[cfg(feature = "gui")]
 use chip8::{renderer::Renderer, Chip8};
+
 fn main() {
-    Chip8::new(&mut Renderer::new("Chip8/SuperChip Interpreter", 1024, 720).unwrap()).run();
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Some(headless) = args.headless.as_ref() {
+        match chip8::headless::run(&args.options, headless) {
+            Ok(code) => process::exit(code),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    if let Some(tui) = args.tui.as_ref() {
+        match chip8::tui::run(&args.options, tui) {
+            Ok(code) => process::exit(code),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+
+    run_window(args);
+}
+
+// the window with the debugger, sdl's software renderer if opengl is missing
+#[cfg(feature = "gui")]
+fn run_window(args: cli::Args) {
+    if !args.software {
+        let (width, height) = args.window_size();
+
+        match Renderer::new("Chip8/SuperChip Interpreter", width, height) {
+            Ok(mut renderer) => {
+                if args.fullscreen {
+                    if let Err(err) = renderer.set_fullscreen(true) {
+                        eprintln!("warning: cannot switch to fullscreen\n{}", err);
+                    }
+                }
+
+                Chip8::new(&mut renderer, args.options).run();
+                return;
+            }
+            Err(err) => eprintln!(
+                "warning: cannot create an OpenGL 3.3 window, using the software renderer\n{}",
+                err
+            ),
+        }
+    }
+
+    match chip8::software::run(&args.options, args.scale.unwrap_or(10), args.fullscreen) {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_window(_args: cli::Args) {
+    eprintln!("error: built without the gui feature, there is no window to run the rom in");
+    process::exit(2);
+}
+
+// the settings files give the defaults, options on the command line override them
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn parse_args() -> Result<Option<cli::Args>, String> {
+    let mut args = match cli::parse(std::env::args().skip(1))? {
+        Some(args) => args,
+        None => return Ok(None),
+    };
+
+    // headless runs give the same result on every machine, so they ignore the settings files
+    if args.headless.is_some() {
+        return Ok(Some(args));
+    }
+
+    let settings = Settings::load().unwrap_or_else(|err| {
+        eprintln!("warning: ignoring the settings file\n{}", err);
+        Settings::default()
+    });
+
+    // the rom decides its own quirks, speed and palette
+    let rom = match args.options.rom_path.as_ref().map(fs::read) {
+        Some(Ok(rom)) => rom,
+        _ => {
+            // an unreadable rom is reported when it is opened
+            args.apply_settings(&settings);
+            return Ok(Some(args));
+        }
+    };
+
+    let database = Database::load().unwrap_or_else(|err| {
+        eprintln!("warning: ignoring the rom database\n{}", err);
+        Database::default()
+    });
+
+    let roms = RomSettings::load().unwrap_or_else(|err| {
+        eprintln!("warning: ignoring the rom settings file\n{}", err);
+        RomSettings::default()
+    });
+
+    args.apply_settings(&rom_settings(settings, &database, &roms, &rom));
+
+    Ok(Some(args))
+}
+
+// without a frontend only headless runs work and they dont read the settings files
+#[cfg(not(any(feature = "gui", feature = "tui")))]
+fn parse_args() -> Result<Option<cli::Args>, String> {
+    cli::parse(std::env::args().skip(1))
+}
+
+// the rom database knows the platform, your own changes to the rom in roms.toml override it
+#[cfg(any(feature = "gui", feature = "tui"))]
+fn rom_settings(
+    settings: Settings,
+    database: &Database,
+    roms: &RomSettings,
+    rom: &[u8],
+) -> Settings {
+    let hash = rom_hash(rom);
+
+    let base = match database.get(&hash) {
+        Some(info) => info.apply(settings),
+        None => settings,
+    };
+
+    roms.get(&hash, &base)
+        .unwrap_or_else(|err| {
+            eprintln!("warning: ignoring the rom settings file\n{}", err);
+            None
+        })
+        .unwrap_or(base)
+}
+
+#[cfg(all(test, any(feature = "gui", feature = "tui")))]
+mod tests {
+    use chip8::Color;
+    #[cfg(feature = "gui")]
+    use chip8::{
+        layout::{Layout, Rect},
+        MENU_BAR_HEIGHT,
+    };
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> cli::Args {
+        cli::parse(args.iter().map(|arg| arg.to_string()))
+            .unwrap()
+            .unwrap()
+    }
+
+    // the command line beats the rom settings, which beat settings.toml
+    #[test]
+    fn options_take_precedence_over_files() {
+        let settings =
+            Settings::from_toml("cycles_per_frame = 20\nbackground = \"#102030\"").unwrap();
+
+        let rom = [0x12, 0x00];
+        let roms = RomSettings::from_toml(&format!(
+            "[{}]\ncycles_per_frame = 30\nforeground = \"#405060\"",
+            rom_hash(&rom)
+        ))
+        .unwrap();
+
+        let stored = rom_settings(settings, &Database::default(), &roms, &rom);
+
+        let mut args = parse(&["--cycles", "40"]);
+        args.apply_settings(&stored);
+        assert_eq!(args.options.cycles_per_frame, 40);
+        assert!(args.options.color_on == Color::new(0x40, 0x50, 0x60));
+        assert!(args.options.color_off == Color::new(0x10, 0x20, 0x30));
+
+        let mut args = parse(&[]);
+        args.apply_settings(&stored);
+        assert_eq!(args.options.cycles_per_frame, 30);
+
+        // other roms only get settings.toml
+        let stored = rom_settings(settings, &Database::default(), &roms, &[0x00, 0xE0]);
+
+        let mut args = parse(&["--fg", "ffffff"]);
+        args.apply_settings(&stored);
+        assert_eq!(args.options.cycles_per_frame, 20);
+        assert!(args.options.color_on == Color::new(0xFF, 0xFF, 0xFF));
+
+        // a value on the command line wins even if it is the default
+        let mut args = parse(&["--cycles", "60"]);
+        args.apply_settings(&stored);
+        assert_eq!(args.options.cycles_per_frame, 60);
+    }
+
+    // the game view gets the requested scale in the top left quarter of the window
+    #[cfg(feature = "gui")]
+    #[test]
+    fn window_fits_the_scale() {
+        for scale in 1..16 {
+            let args = parse(&["--scale", &scale.to_string()]);
+            let (width, height) = args.window_size();
+
+            let area = Rect::new(
+                0,
+                MENU_BAR_HEIGHT as i32,
+                width / 2,
+                (height / 2).saturating_sub(MENU_BAR_HEIGHT),
+            );
+
+            let view = Layout::default().fit(area, 64, 32);
+            assert_eq!((view.width, view.height), (64 * scale, 32 * scale));
+        }
+    }
 }
@@ -0,0 +1,218 @@
+// runs the bundled test roms through the headless core
+// test_opcode and c8_test check the interpreter themselves and draw OK for every passed test,
+// that verdict is checked directly and doesn't rely on images produced by this interpreter
+// the final display is also compared against the ascii art golden images in
+// tests/golden/<profile>/<rom>.txt, these were generated by this interpreter and only catch
+// regressions, set UPDATE_GOLDEN=1 to rewrite them after an intended change
+
+use std::{env, fs, path::PathBuf};
+
+use chip8::{
+    fault::ExecutionFault,
+    headless::{self, InputScript},
+    image,
+    machine::Machine,
+    options::{Config, Options},
+};
+
+struct TestRom {
+    path: &'static str,
+    frames: u32,
+    input: &'static str, // headless input script
+    profiles: &'static [&'static str],
+    quirks: fn(&mut Config), // what the rom relies on under every profile, like a database entry
+    passed: Option<(&'static [&'static str], usize)>, // glyph drawn for a passed test and its count
+}
+
+fn profile_quirks(_: &mut Config) {}
+
+// c8_test stores registers with FX55 and reads them back with FX65 without setting I again
+fn keep_i(config: &mut Config) {
+    config.store_behaviour = false;
+}
+
+// test_opcode draws OK next to every opcode it checked, NO otherwise
+const SMALL_OK: [&str; 4] = ["###.#.#", "#.#.##.", "#.#.#.#", "###.#.#"];
+
+// c8_test draws a single OK in the middle of the screen, or ERROR and a test number
+const LARGE_OK: [&str; 5] = [
+    ".##....#..#",
+    "#..#...#.#.",
+    "#..#...##..",
+    "#..#...#.#.",
+    ".##....#..#",
+];
+
+const TEST_ROMS: [TestRom; 4] = [
+    TestRom {
+        path: "roms/Chip8/test_opcode.ch8",
+        frames: 120,
+        input: "",
+        profiles: &["chip8", "schip"],
+        quirks: profile_quirks,
+        passed: Some((&SMALL_OK, 18)),
+    },
+    TestRom {
+        // finds out the shift behaviour itself, reports error 14 if FX55/FX65 move I
+        path: "roms/Chip8/c8_test.ch8",
+        frames: 120,
+        input: "",
+        profiles: &["chip8", "schip"],
+        quirks: keep_i,
+        passed: Some((&LARGE_OK, 1)),
+    },
+    TestRom {
+        // 2 increments the counter, 5 loads it into the delay timer and shows the countdown
+        path: "roms/Chip8/delay_timer_test.ch8",
+        frames: 30,
+        input: "10 2\n15 .\n20 5\n21 .\n",
+        profiles: &["chip8", "schip"],
+        quirks: profile_quirks,
+        passed: None,
+    },
+    TestRom {
+        path: "roms/Chip8/random_number_test.ch8",
+        frames: 60,
+        input: "",
+        profiles: &["chip8", "schip"],
+        quirks: profile_quirks,
+        passed: None,
+    },
+];
+
+fn run_rom(rom: &TestRom, profile: &str) -> (Machine, u32, Option<ExecutionFault>) {
+    let mut config = Config::from_profile(profile).unwrap();
+    (rom.quirks)(&mut config);
+
+    let options = Options {
+        config,
+        seed: Some(0),
+        ..Options::default()
+    };
+
+    let data = fs::read(root().join(rom.path)).unwrap();
+
+    let mut machine = Machine::new(&options);
+    machine.load_rom(&data).unwrap();
+
+    let script = InputScript::parse(rom.input).unwrap();
+    let (frames, fault) = headless::run_frames(
+        &mut machine,
+        options.cycles_per_frame,
+        rom.frames,
+        &script,
+        |_| {},
+    );
+
+    (machine, frames, fault)
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn golden_path(rom: &TestRom, profile: &str) -> PathBuf {
+    let name = PathBuf::from(rom.path).file_stem().unwrap().to_owned();
+
+    root()
+        .join("tests/golden")
+        .join(profile)
+        .join(name)
+        .with_extension("txt")
+}
+
+// number of places the glyph appears on the ascii art screen
+fn count_glyph(screen: &str, glyph: &[&str]) -> usize {
+    let rows: Vec<&str> = screen.lines().collect();
+    let width = glyph[0].len();
+    let mut count = 0;
+
+    for y in 0..=rows.len().saturating_sub(glyph.len()) {
+        for x in 0..=rows[y].len().saturating_sub(width) {
+            let found = glyph.iter().enumerate().all(|(i, line)| {
+                rows.get(y + i).and_then(|row| row.get(x..x + width)) == Some(line)
+            });
+
+            if found {
+                count += 1;
+            }
+        }
+    }
+
+    count
+}
+
+#[test]
+fn test_roms_report_success() {
+    for rom in TEST_ROMS.iter() {
+        let (glyph, expected) = match rom.passed {
+            Some(passed) => passed,
+            None => continue,
+        };
+
+        for profile in rom.profiles.iter() {
+            let (machine, _, _) = run_rom(rom, profile);
+            let screen = image::to_ascii(&machine);
+
+            assert_eq!(
+                count_glyph(&screen, glyph),
+                expected,
+                "{} ({}) reported a failure\n{}",
+                rom.path,
+                profile,
+                screen
+            );
+        }
+    }
+}
+
+#[test]
+fn test_roms_match_golden_images() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    for rom in TEST_ROMS.iter() {
+        for profile in rom.profiles.iter() {
+            let (machine, _, fault) = run_rom(rom, profile);
+
+            if let Some(fault) = fault {
+                panic!("{} ({}) raised a fault: {}", rom.path, profile, fault);
+            }
+
+            let screen = image::to_ascii(&machine);
+            let path = golden_path(rom, profile);
+
+            if update {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, &screen).unwrap();
+                continue;
+            }
+
+            let golden =
+                fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}\npath: {:?}", err, path));
+
+            if golden != screen {
+                failures.push(format!(
+                    "{} ({})\nexpected:\n{}\ngot:\n{}",
+                    rom.path, profile, golden, screen
+                ));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_opcode_runs_to_completion() {
+    // test_opcode ends in a jump to itself after drawing every result
+    for profile in Config::PROFILES.iter() {
+        let (machine, frames, _) = run_rom(&TEST_ROMS[0], profile);
+
+        assert_eq!(frames, TEST_ROMS[0].frames);
+        assert_eq!(
+            machine.pc, 0x3DC,
+            "test_opcode should end in its final loop"
+        );
+    }
+}
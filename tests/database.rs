@@ -0,0 +1,85 @@
+// roms known to the community chip-8-database configure themselves
+
+#![cfg(any(feature = "gui", feature = "tui"))]
+
+use chip8::{database::Database, settings::Settings, Color};
+
+const PROGRAMS: &str = r##"[
+    {
+        "title": "Pong",
+        "description": "Two paddles and a ball",
+        "release": "1990",
+        "authors": ["Paul Vervalin"],
+        "roms": {
+            "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                "file": "pong.ch8",
+                "platforms": ["superchip", "originalChip8"],
+                "tickrate": 30,
+                "colors": { "pixels": ["#000000", "#ffaa00"] },
+                "keys": { "player1Down": 4, "player1Up": 1 }
+            }
+        }
+    },
+    {
+        "title": "Clipping",
+        "roms": {
+            "0000000000000000000000000000000000000001": {
+                "platforms": ["xochip"],
+                "quirkyPlatforms": { "xochip": { "wrap": false } }
+            }
+        }
+    }
+]"##;
+
+const PLATFORMS: &str = r#"[
+    {
+        "id": "superchip",
+        "quirks": { "shift": true, "memoryLeaveIUnchanged": true, "wrap": false },
+        "defaultTickrate": 20
+    }
+]"#;
+
+#[test]
+fn known_roms_bring_their_settings() {
+    let database = Database::parse(PROGRAMS, Some(PLATFORMS)).unwrap();
+    assert_eq!(database.len(), 2);
+
+    let info = database
+        .get("a9993e364706816aba3e25717850c26c9cd0d89d")
+        .unwrap();
+    assert_eq!(info.title, "Pong");
+    assert_eq!(info.platform.as_deref(), Some("superchip"));
+    assert_eq!(
+        info.keys,
+        vec![("player1Up".to_string(), 1), ("player1Down".to_string(), 4)]
+    );
+
+    // the rom tickrate wins over the platform default
+    let settings = info.apply(Settings::default());
+    assert_eq!(settings.cycles_per_frame, 30);
+    assert!(settings.color_off == Color::new(0x00, 0x00, 0x00));
+    assert!(settings.color_on == Color::new(0xFF, 0xAA, 0x00));
+    assert!(!settings.config.shift_behaviour);
+    assert!(!settings.config.draw_behaviour);
+    assert!(!settings.config.store_behaviour);
+}
+
+#[test]
+fn quirky_roms_fix_their_platform() {
+    let database = Database::parse(PROGRAMS, None).unwrap();
+
+    let info = database
+        .get("0000000000000000000000000000000000000001")
+        .unwrap();
+    let settings = info.apply(Settings::default());
+
+    // xochip wraps, this rom needs clipping
+    assert!(!settings.config.draw_behaviour);
+    assert!(settings.config.shift_behaviour);
+    assert_eq!(
+        settings.cycles_per_frame,
+        Settings::default().cycles_per_frame
+    );
+
+    assert!(Database::parse("{}", None).is_err());
+}
@@ -0,0 +1,54 @@
+// display filters turn the lit pixels into a brightness per pixel
+
+use chip8::{
+    filter::{DisplayFilter, Filter},
+    machine::Machine,
+    options::Options,
+};
+
+fn apply(filter: &mut DisplayFilter, machine: &mut Machine, lit: bool) -> f32 {
+    machine.screen_buffer[0] = lit as u8;
+    filter.apply(machine)[0]
+}
+
+#[test]
+fn deflicker_delays_clears() {
+    let mut machine = Machine::new(&Options::default());
+    let mut filter = DisplayFilter::default();
+    filter.filter = Filter::Deflicker;
+    filter.clear_delay = 2;
+
+    assert_eq!(apply(&mut filter, &mut machine, true), 1.0);
+    assert_eq!(apply(&mut filter, &mut machine, false), 1.0);
+    assert_eq!(apply(&mut filter, &mut machine, false), 1.0);
+    assert_eq!(apply(&mut filter, &mut machine, false), 0.0);
+
+    // redrawn before the delay ran out
+    assert_eq!(apply(&mut filter, &mut machine, true), 1.0);
+    assert_eq!(apply(&mut filter, &mut machine, false), 1.0);
+}
+
+#[test]
+fn phosphor_fades_by_half_life() {
+    let mut machine = Machine::new(&Options::default());
+    let mut filter = DisplayFilter::default();
+    filter.filter = Filter::Phosphor;
+    filter.half_life = 2.0;
+
+    assert_eq!(apply(&mut filter, &mut machine, true), 1.0);
+    apply(&mut filter, &mut machine, false);
+
+    let brightness = apply(&mut filter, &mut machine, false);
+    assert!((brightness - 0.5).abs() < 1e-4);
+}
+
+#[test]
+fn blend_averages_frames() {
+    let mut machine = Machine::new(&Options::default());
+    let mut filter = DisplayFilter::default();
+    filter.filter = Filter::Blend;
+
+    assert_eq!(apply(&mut filter, &mut machine, true), 0.5);
+    assert_eq!(apply(&mut filter, &mut machine, true), 1.0);
+    assert_eq!(apply(&mut filter, &mut machine, false), 0.5);
+}
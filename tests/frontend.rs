@@ -0,0 +1,144 @@
+// drives the machine through the frontend traits with the in-memory implementations
+
+use chip8::{
+    frontend::{self, MemoryAudio, MemoryDisplay, MemoryInput, NullAudio, NullDisplay},
+    machine::Machine,
+    options::Options,
+};
+
+fn machine(rom: &[u8]) -> Machine {
+    let mut machine = Machine::new(&Options {
+        seed: Some(0),
+        ..Options::default()
+    });
+    machine.load_rom(rom).unwrap();
+
+    machine
+}
+
+#[test]
+fn sound_timer_beeps_once() {
+    // LD V0, 5; LD ST, V0; JP 0x204
+    let mut machine = machine(&[0x60, 0x05, 0xF0, 0x18, 0x12, 0x04]);
+    let mut audio = MemoryAudio::default();
+
+    for _ in 0..10 {
+        frontend::run_frame(
+            &mut machine,
+            10,
+            1.0 / 60.0,
+            &mut MemoryInput::default(),
+            &mut audio,
+            &mut NullDisplay,
+        )
+        .unwrap();
+    }
+
+    assert_eq!(audio.beeps, 1);
+    assert!(!audio.beeping);
+}
+
+#[test]
+fn keys_reach_the_machine() {
+    // LD V0, K; LD F, V0; DRW V1, V1, 5; JP 0x206
+    let mut machine = machine(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06]);
+
+    let mut input = MemoryInput::default();
+    let mut display = MemoryDisplay::default();
+
+    let mut run_frame = |machine: &mut Machine, input: &mut MemoryInput| {
+        frontend::run_frame(machine, 10, 1.0 / 60.0, input, &mut NullAudio, &mut display).unwrap();
+    };
+
+    run_frame(&mut machine, &mut input);
+    assert!(machine.waiting_key_input);
+
+    input.keys[0x7] = true;
+    run_frame(&mut machine, &mut input);
+
+    assert_eq!(machine.v[0], 0x7);
+    assert_eq!(display.frames, 2);
+
+    // top row of the 7 glyph is 0xF0
+    let row: Vec<bool> = display.pixels[..8].to_vec();
+    assert_eq!(row, [true, true, true, true, false, false, false, false]);
+}
+
+#[test]
+fn before_step_ends_the_frame_early() {
+    // LD V0, 1; LD V1, 2; LD V2, 3; JP 0x206
+    let mut machine = machine(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x12, 0x06]);
+    let mut display = MemoryDisplay::default();
+
+    // stop at 0x204 like a breakpoint
+    frontend::run_frame_with(
+        &mut machine,
+        10,
+        1.0 / 60.0,
+        &mut MemoryInput::default(),
+        &mut NullAudio,
+        &mut display,
+        |machine| machine.pc != 0x204,
+    )
+    .unwrap();
+
+    assert_eq!(machine.pc, 0x204);
+    assert_eq!(machine.v[..3], [1, 2, 0]);
+
+    // the rest of the frame still runs
+    assert_eq!(display.frames, 1);
+}
+
+#[test]
+fn polled_keys_last_one_frame() {
+    // LD V0, 5; SKP V0; JP 0x202; JP 0x206
+    let mut machine = machine(&[0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x12, 0x06]);
+    let mut input = MemoryInput::default();
+
+    let run_frame = |machine: &mut Machine, input: &mut MemoryInput| {
+        frontend::run_frame(
+            machine,
+            10,
+            1.0 / 60.0,
+            input,
+            &mut NullAudio,
+            &mut NullDisplay,
+        )
+        .unwrap();
+    };
+
+    run_frame(&mut machine, &mut input);
+    let polled: Vec<usize> = (0..16).filter(|key| machine.polled_keys[*key]).collect();
+    assert_eq!(polled, [0x5]);
+
+    input.keys[0x5] = true;
+    run_frame(&mut machine, &mut input);
+    assert_eq!(machine.pc, 0x206);
+
+    // the rom stopped checking
+    run_frame(&mut machine, &mut input);
+    assert_eq!(machine.polled_keys, [false; 16]);
+}
+
+#[test]
+fn collisions_ignore_the_palette() {
+    // LD F, V0; DRW V0, V0, 5; DRW V0, V0, 5; JP 0x206
+    let mut machine = machine(&[0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x06]);
+    machine.color_on = machine.color_off;
+
+    let mut display = MemoryDisplay::default();
+
+    frontend::run_frame(
+        &mut machine,
+        3,
+        1.0 / 60.0,
+        &mut MemoryInput::default(),
+        &mut NullAudio,
+        &mut display,
+    )
+    .unwrap();
+
+    // the second draw erased the first one
+    assert_eq!(machine.v[0xF], 1);
+    assert!(display.pixels.iter().all(|pixel| !pixel));
+}
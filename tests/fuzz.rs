@@ -0,0 +1,333 @@
+// property based tests that run random roms and instructions against the headless core
+// the interpreter must never panic, invalid programs only raise faults
+
+use chip8::{
+    fault::{ExecutionFault, FaultKind, FaultPolicy},
+    machine::{Machine, HEIGHT, MEMORY_SIZE, S_HEIGHT, S_WIDTH, WIDTH},
+    options::{Config, Options},
+};
+
+use proptest::prelude::*;
+
+fn machine(config: u8) -> Machine {
+    Machine::new(&Options {
+        config: Config {
+            shift_behaviour: config & 0x1 != 0,
+            draw_behaviour: config & 0x2 != 0,
+            store_behaviour: config & 0x4 != 0,
+        },
+        seed: Some(0),
+        ..Options::default()
+    })
+}
+
+fn set_policy(machine: &mut Machine, policy: FaultPolicy) {
+    for kind in FaultKind::ALL.iter() {
+        *machine.fault_policies.get_mut(*kind) = policy;
+    }
+}
+
+fn set_keys(machine: &mut Machine, keys: u16) {
+    for (index, pressed) in machine.keys.iter_mut().enumerate() {
+        *pressed = (keys >> index) & 0x1 != 0;
+    }
+}
+
+fn check_invariants(machine: &Machine) {
+    assert!(machine.sp <= machine.stack.len(), "sp = {}", machine.sp);
+
+    assert!(
+        (machine.width, machine.height) == (WIDTH, HEIGHT)
+            || (machine.width, machine.height) == (S_WIDTH, S_HEIGHT),
+        "invalid resolution {}x{}",
+        machine.width,
+        machine.height
+    );
+}
+
+// run until the rom stops, a fault is trapped or the cycle budget runs out
+fn run(machine: &mut Machine, cycles: u32) -> Option<ExecutionFault> {
+    for cycle in 0..cycles {
+        if !machine.is_running() {
+            break;
+        }
+
+        let pc = machine.pc;
+
+        if let Err(fault) = machine.step() {
+            assert_eq!(machine.pc, pc, "a trapped fault must not advance pc");
+            return Some(fault);
+        }
+
+        if cycle % 60 == 0 {
+            machine.update_timers(1.0 / 60.0);
+        }
+
+        check_invariants(machine);
+    }
+
+    None
+}
+
+proptest! {
+    #[test]
+    fn random_roms_never_panic(
+        rom in prop::collection::vec(any::<u8>(), 0..=MEMORY_SIZE - 0x200),
+        config in 0u8..8,
+        policy in prop::sample::select(FaultPolicy::ALL.to_vec()),
+        keys in any::<u16>(),
+        cycles in 0u32..5000,
+    ) {
+        let mut machine = machine(config);
+        set_policy(&mut machine, policy);
+        machine.load_rom(&rom).unwrap();
+        set_keys(&mut machine, keys);
+
+        run(&mut machine, cycles);
+    }
+
+    #[test]
+    fn instructions_from_random_states_never_panic(
+        opcodes in prop::collection::vec(any::<u16>(), 1..64),
+        v in any::<[u8; 16]>(),
+        r_address in any::<u16>(),
+        pc in 0x200u16..MEMORY_SIZE as u16,
+        sp in 0usize..=16,
+        config in 0u8..8,
+        policy in prop::sample::select(FaultPolicy::ALL.to_vec()),
+        keys in any::<u16>(),
+    ) {
+        let mut machine = machine(config);
+        set_policy(&mut machine, policy);
+        machine.load_rom(&[]).unwrap();
+        set_keys(&mut machine, keys);
+
+        // place the instructions at pc, anything that doesnt fit is dropped
+        for (index, opcode) in opcodes.iter().enumerate() {
+            let address = pc as usize + index * 2;
+
+            if address + 1 < MEMORY_SIZE {
+                machine.memory[address] = (opcode >> 8) as u8;
+                machine.memory[address + 1] = *opcode as u8;
+            }
+        }
+
+        machine.v = v;
+        machine.r_address = r_address;
+        machine.pc = pc;
+        machine.sp = sp;
+
+        run(&mut machine, opcodes.len() as u32);
+    }
+
+    #[test]
+    fn stepping_back_restores_the_state(
+        rom in prop::collection::vec(any::<u8>(), 0..512),
+        config in 0u8..8,
+        policy in prop::sample::select(FaultPolicy::ALL.to_vec()),
+        cycles in 0usize..200,
+    ) {
+        let mut machine = machine(config);
+        set_policy(&mut machine, policy);
+        machine.load_rom(&rom).unwrap();
+
+        let memory = machine.memory.clone();
+        let screen = machine.screen_buffer.clone();
+        let (v, pc, sp, r_address) = (machine.v, machine.pc, machine.sp, machine.r_address);
+
+        let mut executed = 0;
+
+        while executed < cycles && machine.is_running() && machine.step().is_ok() {
+            executed += 1;
+        }
+
+        prop_assert_eq!(machine.history.len(), executed);
+
+        for _ in 0..executed {
+            prop_assert!(machine.step_back());
+        }
+
+        prop_assert!(!machine.step_back());
+        prop_assert!(machine.memory[..] == memory[..]);
+        prop_assert!(machine.screen_buffer[..] == screen[..]);
+        prop_assert_eq!((machine.v, machine.pc, machine.sp, machine.r_address), (v, pc, sp, r_address));
+        prop_assert!(machine.is_running());
+    }
+}
+
+// crashes found by the harness, every one of these used to panic
+mod regressions {
+    use super::*;
+
+    fn run_program(program: &[u8]) -> (Machine, Option<ExecutionFault>) {
+        let mut machine = machine(0x7);
+        machine.load_rom(program).unwrap();
+
+        let fault = run(&mut machine, 100);
+
+        (machine, fault)
+    }
+
+    #[test]
+    fn fx33_at_end_of_memory() {
+        // LD I, 0xFFF; LD B, V0
+        let (_, fault) = run_program(&[0xAF, 0xFF, 0xF0, 0x33]);
+        assert_eq!(
+            fault,
+            Some(ExecutionFault::MemoryOutOfRange {
+                address: 0xFFF,
+                len: 3
+            })
+        );
+    }
+
+    #[test]
+    fn fx55_past_end_of_memory() {
+        // LD I, 0xFFA; LD [I], VF
+        let (_, fault) = run_program(&[0xAF, 0xFA, 0xFF, 0x55]);
+        assert_eq!(
+            fault,
+            Some(ExecutionFault::MemoryOutOfRange {
+                address: 0xFFA,
+                len: 16
+            })
+        );
+    }
+
+    #[test]
+    fn fx65_past_end_of_memory() {
+        // LD I, 0xFFA; LD VF, [I]
+        let (_, fault) = run_program(&[0xAF, 0xFA, 0xFF, 0x65]);
+        assert_eq!(
+            fault,
+            Some(ExecutionFault::MemoryOutOfRange {
+                address: 0xFFA,
+                len: 16
+            })
+        );
+    }
+
+    #[test]
+    fn fx1e_can_point_past_memory() {
+        // I is 16 bits wide like on the cosmac vip, FX1E doesn't wrap it at 0xFFF
+        // LD I, 0xFFE; LD V0, 4; ADD I, V0; LD V0, [I]
+        let (machine, fault) = run_program(&[0xAF, 0xFE, 0x60, 0x04, 0xF0, 0x1E, 0xF0, 0x65]);
+        assert_eq!(machine.r_address, 0x1002);
+
+        // the fault is raised when memory is accessed through it
+        assert_eq!(
+            fault,
+            Some(ExecutionFault::MemoryOutOfRange {
+                address: 0x1002,
+                len: 1
+            })
+        );
+    }
+
+    #[test]
+    fn fx1e_wraps_at_16_bits() {
+        let mut machine = machine(0x7);
+        machine.load_rom(&[0xF0, 0x1E]).unwrap(); // ADD I, V0
+
+        machine.r_address = 0xFFFF;
+        machine.v[0] = 0x02;
+        machine.step().unwrap();
+
+        assert_eq!(machine.r_address, 0x0001);
+    }
+
+    #[test]
+    fn pc_at_last_byte_of_memory() {
+        // JP 0xFFF
+        let (machine, fault) = run_program(&[0x1F, 0xFF]);
+        assert_eq!(
+            fault,
+            Some(ExecutionFault::MemoryOutOfRange {
+                address: 0xFFF,
+                len: 2
+            })
+        );
+        assert_eq!(machine.pc, 0xFFF);
+    }
+
+    #[test]
+    fn large_sprite_at_end_of_memory() {
+        // HIGH; LD I, 0xFF0; DRW V0, V0, 0
+        let (_, fault) = run_program(&[0x00, 0xFF, 0xAF, 0xF0, 0xD0, 0x00]);
+        assert_eq!(
+            fault,
+            Some(ExecutionFault::MemoryOutOfRange {
+                address: 0xFF0,
+                len: 32
+            })
+        );
+    }
+}
+
+mod policies {
+    use super::*;
+
+    #[test]
+    fn trap_leaves_state_untouched() {
+        let mut machine = machine(0x7);
+        machine.load_rom(&[0x00, 0xEE]).unwrap(); // RET
+
+        assert_eq!(machine.step(), Err(ExecutionFault::StackUnderflow));
+        assert_eq!(machine.pc, 0x200);
+        assert_eq!(machine.sp, 0);
+        assert!(machine.is_running());
+    }
+
+    #[test]
+    fn wrap_stack_overflow() {
+        let mut machine = machine(0x7);
+        set_policy(&mut machine, FaultPolicy::Wrap);
+        machine.load_rom(&[0x22, 0x00]).unwrap(); // CALL 0x200
+
+        for _ in 0..17 {
+            machine.step().unwrap();
+        }
+
+        assert_eq!(machine.sp, 1);
+        assert_eq!(machine.pc, 0x200);
+    }
+
+    #[test]
+    fn wrap_memory_access() {
+        let mut machine = machine(0x7);
+        set_policy(&mut machine, FaultPolicy::Wrap);
+        machine.load_rom(&[0xAF, 0xFF, 0xF0, 0x33]).unwrap(); // LD I, 0xFFF; LD B, V0
+
+        machine.v[0] = 123;
+        machine.step().unwrap();
+        machine.step().unwrap();
+
+        assert_eq!(machine.memory[0xFFF], 1);
+        assert_eq!(machine.memory[0x000], 2);
+        assert_eq!(machine.memory[0x001], 3);
+    }
+
+    #[test]
+    fn ignore_skips_instruction() {
+        let mut machine = machine(0x7);
+        set_policy(&mut machine, FaultPolicy::Ignore);
+        machine.load_rom(&[0xFF, 0xFF, 0x60, 0x01]).unwrap(); // unknown; LD V0, 1
+
+        machine.step().unwrap();
+        machine.step().unwrap();
+
+        assert_eq!(machine.v[0], 1);
+        assert_eq!(machine.pc, 0x204);
+    }
+
+    #[test]
+    fn font_digit_wraps_by_default() {
+        let mut machine = machine(0x7);
+        machine.load_rom(&[0xF0, 0x29]).unwrap(); // LD F, V0
+
+        machine.v[0] = 0x12;
+        machine.step().unwrap();
+
+        assert_eq!(machine.r_address, 0x2 * 5);
+    }
+}
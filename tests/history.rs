@@ -0,0 +1,55 @@
+// instructions skipped from the fault view can be stepped back like executed ones
+
+use chip8::{fault::ExecutionFault, machine::Machine, options::Options};
+
+#[test]
+fn skipped_instructions_are_recorded() {
+    // LD V0, 1; 0x0000 is an unknown opcode; LD V1, 2
+    let mut machine = Machine::new(&Options::default());
+    machine
+        .load_rom(&[0x60, 0x01, 0x00, 0x00, 0x61, 0x02])
+        .unwrap();
+
+    machine.step().unwrap();
+    assert_eq!(machine.step(), Err(ExecutionFault::UnknownOpcode(0x0000)));
+    assert_eq!(machine.pc, 0x202);
+
+    machine.skip_instruction();
+    machine.step().unwrap();
+    assert_eq!((machine.pc, machine.v[1]), (0x206, 2));
+    assert_eq!(machine.history.len(), 3);
+
+    machine.step_back();
+    machine.step_back();
+    assert_eq!(machine.pc, 0x202);
+}
+
+#[test]
+fn stepping_back_restores_timers_and_rng() {
+    // LD V0, 5; LD DT, V0; RND V1, 0xFF; LD DT, V0
+    let mut machine = Machine::new(&Options::default());
+    machine
+        .load_rom(&[0x60, 0x05, 0xF0, 0x15, 0xC1, 0xFF, 0xF0, 0x15])
+        .unwrap();
+    let interval = machine.dt_interval;
+
+    machine.step().unwrap();
+    machine.step().unwrap();
+    machine.update_timers(interval * 0.75);
+    machine.step().unwrap();
+    let random = machine.v[1];
+    machine.step().unwrap();
+
+    machine.step_back();
+    machine.step_back();
+    assert_eq!((machine.pc, machine.r_delay_timer), (0x204, 5));
+
+    // the same number comes out again
+    machine.step().unwrap();
+    assert_eq!(machine.v[1], random);
+
+    // the quarter interval left before the second LD DT is back
+    machine.step_back();
+    machine.update_timers(interval * 0.5);
+    assert_eq!(machine.r_delay_timer, 4);
+}
@@ -0,0 +1,131 @@
+// key bindings can be changed, named and kept for a single rom
+
+#![cfg(any(feature = "gui", feature = "tui"))]
+
+use chip8::{
+    gamepad::PadInput,
+    keymap::{Bindings, KeyMap},
+    settings::{rom_hash, RomSettings},
+};
+use sdl2::{
+    controller::{Axis, Button},
+    keyboard::Scancode,
+};
+
+#[test]
+fn a_keyboard_key_belongs_to_one_chip8_key() {
+    let mut map = KeyMap::default();
+    assert_eq!(map.keys(5), &[Scancode::W]);
+
+    map.bind(5, Scancode::Up);
+    map.bind(8, Scancode::Down);
+    assert_eq!(map.keys(5), &[Scancode::W, Scancode::Up]);
+
+    // binding it again moves it
+    map.bind(8, Scancode::W);
+    assert_eq!(map.keys(5), &[Scancode::Up]);
+    assert_eq!(map.keys(8), &[Scancode::S, Scancode::Down, Scancode::W]);
+
+    map.unbind(5, Scancode::Up);
+    assert!(map.keys(5).is_empty());
+
+    assert!(KeyMap::from_table(&map.to_table()).unwrap() == map);
+}
+
+#[test]
+fn terminal_characters_follow_the_bindings() {
+    let mut map = KeyMap::default();
+    assert_eq!(map.key_for_char('w'), Some(5));
+    assert_eq!(map.key_for_char('W'), Some(5));
+    assert_eq!(map.key_for_char('1'), Some(1));
+    assert_eq!(map.key_for_char('p'), None);
+
+    map.bind(5, Scancode::P);
+    assert_eq!(map.key_for_char('p'), Some(5));
+
+    // the numpad types digits like the main keyboard
+    let numpad = KeyMap::layout("numpad").unwrap();
+    assert_eq!(numpad.key_for_char('7'), Some(1));
+}
+
+#[test]
+fn controllers_are_bound_like_keys() {
+    let up = PadInput::Axis(Axis::LeftY, false);
+    assert_eq!(up.name(), "lefty-");
+    assert_eq!(PadInput::from_name("lefty-"), Some(up));
+    assert_eq!(
+        PadInput::from_name("dpup"),
+        Some(PadInput::Button(Button::DPadUp))
+    );
+    assert_eq!(PadInput::from_name("lefty"), None);
+
+    // the d-pad and left stick work out of the box
+    let mut map = KeyMap::default();
+    assert_eq!(map.pad(2), &[PadInput::Button(Button::DPadUp), up]);
+
+    map.bind_pad(5, up);
+    map.unbind_pad(2, PadInput::Button(Button::DPadUp));
+    assert!(map.pad(2).is_empty());
+    assert_eq!(map.pad(5), &[PadInput::Button(Button::A), up]);
+
+    assert!(KeyMap::from_table(&map.to_table()).unwrap() == map);
+
+    // files without controller bindings keep the defaults
+    let mut table = map.to_table();
+    table.remove("pad");
+    let loaded = KeyMap::from_table(&table).unwrap();
+    assert!(loaded.pad(2) == KeyMap::default().pad(2));
+    assert!(loaded.keys(5) == map.keys(5));
+}
+
+#[test]
+fn layouts_survive_a_round_trip() {
+    let mut bindings = Bindings::default();
+    assert_eq!(bindings.active(), "keypad");
+    assert!(bindings.select("numpad"));
+    assert!(!bindings.select("arrows"));
+
+    bindings.save_as("arrows");
+    bindings.active_map_mut().bind(5, Scancode::Up);
+
+    let loaded = Bindings::from_toml(&bindings.to_toml()).unwrap();
+    assert_eq!(loaded.active(), "arrows");
+    assert_eq!(
+        loaded.names().collect::<Vec<_>>(),
+        vec!["arrows", "keypad", "numpad"]
+    );
+    assert!(loaded.active_map() == bindings.active_map());
+
+    // built in layouts come back, the others go away
+    bindings.select("numpad");
+    bindings.active_map_mut().unbind(0, Scancode::KpPeriod);
+    bindings.reset_active();
+    assert!(*bindings.active_map() == KeyMap::layout("numpad").unwrap());
+
+    bindings.select("arrows");
+    bindings.reset_active();
+    assert_eq!(bindings.active(), "keypad");
+    assert_eq!(bindings.names().count(), 2);
+
+    assert!(Bindings::from_toml("[layouts.bad]\n0 = [\"NoSuchKey\"]").is_err());
+    assert!(Bindings::from_toml("[layouts.bad]\nG = [\"X\"]").is_err());
+}
+
+#[test]
+fn roms_can_keep_their_own_keys() {
+    let hash = rom_hash(b"abc");
+
+    let mut map = KeyMap::layout("numpad").unwrap();
+    map.bind(5, Scancode::Up);
+
+    let mut roms = RomSettings::default();
+    assert!(roms.keys(&hash).unwrap().is_none());
+
+    roms.set_keys(&hash, "abc.ch8", Some(&map));
+
+    let mut roms = RomSettings::from_toml(&roms.to_toml()).unwrap();
+    assert!(roms.keys(&hash).unwrap().unwrap() == map);
+
+    roms.set_keys(&hash, "abc.ch8", None);
+    assert!(roms.keys(&hash).unwrap().is_none());
+}
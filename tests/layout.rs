@@ -0,0 +1,61 @@
+// the game view keeps square pixels and is centered in its area
+
+use chip8::layout::{Layout, Rect};
+
+#[test]
+fn integer_scale_letterboxes() {
+    let layout = Layout::default();
+
+    // 512 / 64 = 8, 360 / 32 = 11.25
+    let view = layout.fit(Rect::new(0, 0, 512, 360), 64, 32);
+    assert_eq!(view, Rect::new(0, 52, 512, 256));
+
+    // high resolution gets half the scale and the same size
+    let view = layout.fit(Rect::new(0, 0, 512, 360), 128, 64);
+    assert_eq!(view, Rect::new(0, 52, 512, 256));
+
+    // 700 / 64 = 10.9 rounds down to 10
+    let view = layout.fit(Rect::new(100, 0, 700, 700), 64, 32);
+    assert_eq!(view, Rect::new(130, 190, 640, 320));
+}
+
+#[test]
+fn smooth_scale_fills_the_area() {
+    let layout = Layout {
+        integer_scale: false,
+        border: 0,
+    };
+
+    let view = layout.fit(Rect::new(0, 0, 700, 700), 64, 32);
+    assert_eq!(view, Rect::new(0, 175, 700, 350));
+}
+
+#[test]
+fn border_keeps_space() {
+    let layout = Layout {
+        integer_scale: true,
+        border: 16,
+    };
+
+    // without the border 512 would fit at scale 8
+    let view = layout.fit(Rect::new(0, 0, 512, 256), 64, 32);
+    assert_eq!(view, Rect::new(32, 16, 448, 224));
+
+    // never smaller than one window pixel per display pixel
+    let view = layout.fit(Rect::new(0, 0, 40, 20), 64, 32);
+    assert_eq!(view, Rect::new(-12, -6, 64, 32));
+}
+
+#[test]
+fn area_for_fits_the_scale() {
+    let layout = Layout {
+        integer_scale: true,
+        border: 16,
+    };
+
+    let (width, height) = layout.area_for(64, 32, 6);
+    assert_eq!((width, height), (416, 224));
+
+    let view = layout.fit(Rect::new(0, 0, width, height), 64, 32);
+    assert_eq!(view, Rect::new(16, 16, 384, 192));
+}
@@ -0,0 +1,102 @@
+// save states restore the machine exactly, so running on from a loaded state
+// gives the same result as running on from the moment it was saved
+
+use std::fs;
+
+use chip8::{
+    frontend::{self, NullAudio, NullDisplay, NullInput},
+    machine::{Machine, STATE_SIZE},
+    options::Options,
+};
+
+fn machine(path: &str) -> Machine {
+    let mut machine = Machine::new(&Options {
+        seed: Some(0),
+        ..Options::default()
+    });
+    machine.load_rom(&fs::read(path).unwrap()).unwrap();
+
+    machine
+}
+
+fn run(machine: &mut Machine, frames: u32) {
+    for _ in 0..frames {
+        frontend::run_frame(
+            machine,
+            10,
+            1.0 / 60.0,
+            &mut NullInput,
+            &mut NullAudio,
+            &mut NullDisplay,
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn loaded_state_runs_the_same() {
+    let mut machine = machine("roms/Chip8/test_opcode.ch8");
+
+    run(&mut machine, 5);
+    let state = machine.save_state();
+    assert_eq!(state.len(), STATE_SIZE);
+
+    run(&mut machine, 60);
+    let expected = machine.save_state();
+
+    machine.load_state(&state).unwrap();
+    assert_eq!(machine.save_state(), state);
+
+    run(&mut machine, 60);
+    assert_eq!(machine.save_state(), expected);
+}
+
+#[test]
+fn invalid_state_is_rejected() {
+    let mut machine = machine("roms/Chip8/test_opcode.ch8");
+    run(&mut machine, 5);
+
+    let state = machine.save_state();
+
+    assert!(machine.load_state(&state[1..]).is_err());
+
+    let mut corrupted = state.clone();
+    corrupted[0] ^= 0xFF;
+    assert!(machine.load_state(&corrupted).is_err());
+
+    assert_eq!(machine.save_state(), state);
+}
+
+#[test]
+fn random_numbers_continue_from_a_loaded_state() {
+    // C0FF, jump back
+    let rom = [0xC0, 0xFF, 0x12, 0x00];
+
+    let mut machine = Machine::new(&Options {
+        seed: Some(0),
+        ..Options::default()
+    });
+    machine.load_rom(&rom).unwrap();
+
+    let state = machine.save_state();
+
+    let mut numbers = Vec::new();
+    for _ in 0..8 {
+        machine.step().unwrap();
+        machine.step().unwrap();
+        numbers.push(machine.v[0]);
+    }
+
+    // a machine with another seed picks up the sequence of the saved one
+    let mut other = Machine::new(&Options {
+        seed: Some(1),
+        ..Options::default()
+    });
+    other.load_state(&state).unwrap();
+
+    for &number in numbers.iter() {
+        other.step().unwrap();
+        other.step().unwrap();
+        assert_eq!(other.v[0], number);
+    }
+}
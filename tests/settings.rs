@@ -0,0 +1,120 @@
+// the settings file keeps the settings window between sessions
+
+#![cfg(any(feature = "gui", feature = "tui"))]
+
+use chip8::{
+    options::{Config, Options},
+    settings::{rom_hash, RomSettings, Settings},
+    view::View,
+    Color,
+};
+
+#[test]
+fn settings_survive_a_round_trip() {
+    let settings = Settings {
+        config: Config::super_chip(),
+        cycles_per_frame: 500,
+        dt_interval: 1.0 / 30.0,
+        st_interval: 0.1,
+        color_on: Color::new(0xFF, 0x80, 0x00),
+        color_off: Color::new(0x00, 0x00, 0x00),
+        vsync: false,
+        volume: 0.5,
+        frequency: 220.0,
+        view: View {
+            memory: false,
+            ..View::default()
+        },
+    };
+
+    let loaded = Settings::from_toml(&settings.to_toml()).unwrap();
+    assert!(loaded == settings);
+
+    // the open windows are not an option
+    let mut options = Options::default();
+    loaded.apply(&mut options);
+    assert!(Settings::from_options(&options).view == View::default());
+    assert!(
+        Settings {
+            view: settings.view,
+            ..Settings::from_options(&options)
+        } == settings
+    );
+}
+
+#[test]
+fn missing_settings_keep_their_defaults() {
+    let settings = Settings::from_toml("cycles_per_frame = 200\n[quirks]\nwrap = false\n").unwrap();
+
+    let mut expected = Settings {
+        cycles_per_frame: 200,
+        ..Settings::default()
+    };
+    expected.config.draw_behaviour = false;
+
+    assert!(settings == expected);
+    assert!(Settings::from_toml("").unwrap() == Settings::default());
+}
+
+#[test]
+fn command_line_options_are_not_saved() {
+    let file = Settings::from_toml("volume = 0.5\ncycles_per_frame = 200").unwrap();
+
+    // --cycles 1000 on the command line, then the volume is changed in the settings window
+    let mut options = Options::default();
+    file.apply(&mut options);
+    options.cycles_per_frame = 1000;
+
+    let applied = Settings::from_options(&options);
+    let mut current = applied;
+    current.volume = 0.25;
+
+    let saved = file.with_changes(&applied, &current);
+    assert_eq!(saved.cycles_per_frame, 200);
+    assert_eq!(saved.volume, 0.25);
+
+    let mut expected = file;
+    expected.volume = 0.25;
+    assert!(Settings::from_toml(&saved.to_toml()).unwrap() == expected);
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    assert!(Settings::from_toml("cycles_per_frame = -1").is_err());
+    assert!(Settings::from_toml("foreground = \"green\"").is_err());
+    assert!(Settings::from_toml("cycels_per_frame = 60").is_err());
+    assert!(Settings::from_toml("[quirks]\nshift = 1").is_err());
+    assert!(Settings::from_toml("vsync = ").is_err());
+    assert!(Settings::from_toml("[windows]\nconsole = true").is_err());
+}
+
+#[test]
+fn rom_settings_only_keep_what_belongs_to_the_rom() {
+    let hash = rom_hash(b"abc");
+    assert_eq!(hash, "a9993e364706816aba3e25717850c26c9cd0d89d");
+
+    let rom = Settings {
+        config: Config::super_chip(),
+        cycles_per_frame: 1000,
+        color_on: Color::new(0xFF, 0x00, 0x00),
+        volume: 1.0,
+        ..Settings::default()
+    };
+
+    let mut roms = RomSettings::default();
+    roms.set(&hash, "abc.ch8", &rom);
+
+    let roms = RomSettings::from_toml(&roms.to_toml()).unwrap();
+
+    let global = Settings {
+        volume: 0.5,
+        vsync: false,
+        ..Settings::default()
+    };
+
+    // the rom brings its quirks, speed and palette, the rest stays global
+    let loaded = roms.get(&hash, &global).unwrap().unwrap();
+    assert!(loaded == global.with_rom_settings(&rom));
+
+    assert!(roms.get(&rom_hash(b"abd"), &global).unwrap().is_none());
+}